@@ -1,4 +1,5 @@
 #include <Arduino.h>
+#include <ArduinoJson.h>
 #include <ArduinoLog.h>
 #include <FastLED.h>
 #include <WsClient.h>
@@ -7,7 +8,20 @@
 
 CRGB leds[LED_COUNT];
 WsClient wsClient;
+char wsPath[256];
 
+// Matches the server's cap, keeps the timeout in milliseconds from overflowing.
+const unsigned long MAX_HEARTBEAT_SECONDS = 60 * 60;
+
+// Pushed by the server, defaults to what was flashed.
+size_t activeLedCount = LED_COUNT;
+char channelOrder[4] = "RGB";
+unsigned long heartbeatTimeoutMs = 0;
+
+unsigned long lastMessageAt = 0;
+
+void applyConfig(const Payload& payload);
+uint8_t channel(const byte* rgb, char name);
 void safeBoot();
 void setupLEDS();
 void setupWifi();
@@ -21,32 +35,103 @@ void setup()
 	safeBoot();
 	setupLEDS();
 	setupWifi();
+
+	// Identify this controller so the server can track it as a device.
+	snprintf(wsPath, sizeof(wsPath), "%s&device_id=%s&firmware=%s&led_count=%d&channel_order=RGB",
+		WS_PATH, DEVICE_ID, FIRMWARE_VERSION, LED_COUNT);
 }
 
 void loop()
 {
 	while (wsClient.getStatus() == WsClient::Status::DISCONNECTED) {
 		delay(1000);
-		wsClient.connect(WS_HOST, WS_PORT, WS_PATH);
+		wsClient.connect(WS_HOST, WS_PORT, wsPath);
+		lastMessageAt = millis();
+	}
+
+	// The server pings every heartbeat, missing three means the connection is dead.
+	if (heartbeatTimeoutMs > 0 && millis() - lastMessageAt > heartbeatTimeoutMs) {
+		Log.warningln("No heartbeat from the server, reconnecting");
+		wsClient.disconnect();
+		return;
 	}
 
 	bool receivedPayload = wsClient.poll();
 	if (receivedPayload) {
 		Payload payload = wsClient.getLatestPayload();
 		Log.infoln("Received payload (opcode=%d, length=%d)", payload.opcode, payload.length);
+		lastMessageAt = millis();
+
+		// The server pings to measure latency, answer with the same payload.
+		if (payload.opcode == Opcode::PING) {
+			wsClient.send(Opcode::PONG, payload.data, payload.length);
+			return;
+		}
+
+		if (payload.opcode == Opcode::TEXT) {
+			applyConfig(payload);
+			return;
+		}
 
-		for (size_t i = 0; i < LED_COUNT; ++i) {
-			size_t redChannelIndex = i * 3;
-			leds[i] = CRGB(
-				payload.data[redChannelIndex],
-				payload.data[redChannelIndex + 1],
-				payload.data[redChannelIndex + 2]);
+		// The server already paces frames to the configured fps, every frame
+		// that arrives is shown.
+		if (payload.opcode != Opcode::BINARY || payload.length < activeLedCount * 3) {
+			return;
+		}
+
+		for (size_t i = 0; i < activeLedCount; ++i) {
+			const byte* rgb = &payload.data[i * 3];
+			leds[i] = CRGB(channel(rgb, channelOrder[0]), channel(rgb, channelOrder[1]), channel(rgb, channelOrder[2]));
 		}
 
 		FastLED.show();
 	}
 }
 
+/// Applies a `{"config": {..}}` message, leaving fields it doesn't set alone.
+void applyConfig(const Payload& payload)
+{
+	JsonDocument doc;
+	DeserializationError error = deserializeJson(doc, payload.data, payload.length);
+	if (error || !doc["config"].is<JsonObject>()) {
+		return;
+	}
+
+	JsonObject config = doc["config"];
+	if (config["max_brightness"].is<uint8_t>()) {
+		FastLED.setBrightness(config["max_brightness"]);
+	}
+	if (config["led_count"].is<size_t>()) {
+		activeLedCount = min((size_t)config["led_count"], (size_t)LED_COUNT);
+		for (size_t i = activeLedCount; i < LED_COUNT; ++i) {
+			leds[i] = CRGB(0, 0, 0);
+		}
+	}
+	if (config["channel_order"].is<const char*>() && strlen(config["channel_order"]) == 3) {
+		strncpy(channelOrder, config["channel_order"], sizeof(channelOrder));
+	}
+	if (config["heartbeat_seconds"].is<unsigned long>()) {
+		unsigned long heartbeatSeconds = min((unsigned long)config["heartbeat_seconds"], MAX_HEARTBEAT_SECONDS);
+		heartbeatTimeoutMs = heartbeatSeconds * 3000;
+	}
+
+	Log.infoln("[CONFIG] Applied (leds=%d, order=%s, brightness=%d)", activeLedCount, channelOrder, FastLED.getBrightness());
+	FastLED.show();
+}
+
+/// Picks the `R`, `G` or `B` channel of an RGB triplet.
+uint8_t channel(const byte* rgb, char name)
+{
+	switch (name) {
+	case 'G':
+		return rgb[1];
+	case 'B':
+		return rgb[2];
+	default:
+		return rgb[0];
+	}
+}
+
 void safeBoot()
 {
 	for (uint8_t t = 4; t > 0; t--) {
@@ -69,6 +154,10 @@ void setupLEDS()
 		Log.infoln("[LEDS] Showing frame %d", i);
 		FastLED.delay(100);
 	}
+
+	// Frames from the server already went through its output pipeline
+	// (brightness, gamma, white balance), so show them as-is from now on.
+	FastLED.setBrightness(255);
 }
 
 void setupWifi()
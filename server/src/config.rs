@@ -1,8 +1,18 @@
-use std::{env, path::PathBuf};
+use std::{env, path::PathBuf, str::FromStr};
 
 use serde::Deserialize;
 use serde_inline_default::serde_inline_default;
 
+use crate::{
+    layout::{LayoutError, LayoutKind},
+    mqtt::{MqttConfig, MqttTopics},
+    output::{DmxTarget, OutputKind, ParseOutputKindError},
+    palette::{Palette, PaletteError},
+    pipeline::{ChannelOrder, Gamma, OutputSettings, PowerSettings},
+    solar::Coordinates,
+    types::{Color, HexColor},
+};
+
 #[serde_inline_default]
 #[derive(Debug, Deserialize)]
 pub struct Config {
@@ -20,4 +30,478 @@ pub struct Config {
     pub otlp_authorization_header: Option<String>,
     #[serde(default)]
     pub ipinfo_token: Option<String>,
+    #[serde_inline_default(vec![CanvasConfig::default()])]
+    pub canvases: Vec<CanvasConfig>,
+    #[serde_inline_default("default".to_string())]
+    pub default_canvas: String,
+    /// Canvases without a layout are treated as a single row.
+    #[serde(default)]
+    pub layouts: Vec<LayoutConfig>,
+    #[serde(default)]
+    pub latitude: Option<f64>,
+    #[serde(default)]
+    pub longitude: Option<f64>,
+    #[serde_inline_default(32)]
+    pub output_brightness: u8,
+    #[serde_inline_default(2.2)]
+    pub output_gamma_red: f32,
+    #[serde_inline_default(2.2)]
+    pub output_gamma_green: f32,
+    #[serde_inline_default(2.2)]
+    pub output_gamma_blue: f32,
+    #[serde_inline_default(HexColor(Color { red: 255, green: 255, blue: 255 }))]
+    pub output_white_balance: HexColor,
+    #[serde(default)]
+    pub output_channel_order: ChannelOrder,
+    #[serde_inline_default(20.0)]
+    pub output_milliamps_per_channel: f32,
+    #[serde_inline_default(1.0)]
+    pub output_idle_milliamps_per_led: f32,
+    #[serde(default)]
+    pub output_power_budget_milliamps: Option<f32>,
+    /// Every canvas also gets a websocket output unless one is declared here.
+    #[serde(default)]
+    pub outputs: Vec<OutputConfig>,
+    #[serde_inline_default(40)]
+    pub output_fps: u32,
+    /// Superseded by `OUTPUTS`, still honored.
+    #[serde(default)]
+    pub sacn_outputs: Vec<DmxOutputConfig>,
+    #[serde_inline_default(40)]
+    pub sacn_fps: u32,
+    /// Superseded by `OUTPUTS`, still honored.
+    #[serde(default)]
+    pub artnet_outputs: Vec<DmxOutputConfig>,
+    #[serde_inline_default(40)]
+    pub artnet_fps: u32,
+    /// Art-Net is only listened for when at least one input is configured.
+    #[serde(default)]
+    pub artnet_inputs: Vec<ArtnetInputConfig>,
+    #[serde_inline_default("0.0.0.0:6454".to_string())]
+    pub artnet_bind_address: String,
+    /// How long web writes stay locked out after the last Art-Net packet.
+    #[serde_inline_default(5)]
+    pub artnet_hold_seconds: u64,
+    /// DDP is only listened for when an address is set, usually
+    /// `0.0.0.0:4048`.
+    #[serde(default)]
+    pub ddp_bind_address: Option<String>,
+    /// Defaults to the default canvas.
+    #[serde(default)]
+    pub ddp_canvas: Option<String>,
+    #[serde_inline_default(5)]
+    pub ddp_hold_seconds: u64,
+    /// OPC is only listened for when an address is set, usually
+    /// `0.0.0.0:7890`.
+    #[serde(default)]
+    pub opc_bind_address: Option<String>,
+    /// Without any mapping, channel 1 goes to the default canvas.
+    #[serde(default)]
+    pub opc_channels: Vec<OpcChannelConfig>,
+    /// The MQTT bridge only runs when a broker is set.
+    #[serde(default)]
+    pub mqtt_host: Option<String>,
+    #[serde_inline_default(1883)]
+    pub mqtt_port: u16,
+    #[serde(default)]
+    pub mqtt_username: Option<String>,
+    #[serde(default)]
+    pub mqtt_password: Option<String>,
+    #[serde_inline_default(env!("CARGO_CRATE_NAME").to_string())]
+    pub mqtt_client_id: String,
+    #[serde_inline_default(env!("CARGO_CRATE_NAME").to_string())]
+    pub mqtt_topic_prefix: String,
+    #[serde_inline_default("homeassistant".to_string())]
+    pub mqtt_discovery_prefix: String,
+    /// Where configs pushed to devices are saved.
+    #[serde_inline_default(PathBuf::from("devices.json"))]
+    pub device_config_path: PathBuf,
+    /// Team mode is on when at least one team is declared.
+    #[serde(default)]
+    pub teams: Vec<TeamConfig>,
+    /// Defaults to the default canvas.
+    #[serde(default)]
+    pub team_canvas: Option<String>,
+    /// Voting mode is on when a canvas is set, its leds are then voted on in
+    /// rounds instead of painted.
+    #[serde(default)]
+    pub voting_canvas: Option<String>,
+    #[serde_inline_default(30)]
+    pub voting_round_seconds: u64,
+    /// Canvases without a palette can be painted any color.
+    #[serde(default)]
+    pub palettes: Vec<PaletteConfig>,
+    /// Lets webhooks deliver to loopback, private and link-local addresses,
+    /// for receivers on the server's own network.
+    #[serde(default)]
+    pub webhook_allow_private_targets: bool,
+}
+
+impl Config {
+    pub fn coordinates(&self) -> Option<Coordinates> {
+        Some(Coordinates {
+            latitude: self.latitude?,
+            longitude: self.longitude?,
+        })
+    }
+
+    /// `OUTPUTS` along with the outputs declared the older way.
+    pub fn all_outputs(&self) -> Vec<OutputConfig> {
+        let legacy = |outputs: &[DmxOutputConfig], kind: fn(DmxTarget) -> OutputKind, fps| {
+            outputs
+                .iter()
+                .map(move |output| OutputConfig {
+                    canvas: output.canvas.clone(),
+                    kind: kind(output.target.clone()),
+                    fps: Some(fps),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        self.outputs
+            .iter()
+            .cloned()
+            .chain(legacy(&self.sacn_outputs, OutputKind::Sacn, self.sacn_fps))
+            .chain(legacy(
+                &self.artnet_outputs,
+                OutputKind::Artnet,
+                self.artnet_fps,
+            ))
+            .collect()
+    }
+
+    pub fn mqtt(&self) -> Option<MqttConfig> {
+        Some(MqttConfig {
+            host: self.mqtt_host.clone()?,
+            port: self.mqtt_port,
+            username: self.mqtt_username.clone(),
+            password: self.mqtt_password.clone(),
+            topics: MqttTopics {
+                client_id: self.mqtt_client_id.clone(),
+                prefix: self.mqtt_topic_prefix.clone(),
+                discovery_prefix: self.mqtt_discovery_prefix.clone(),
+            },
+        })
+    }
+
+    pub fn output_settings(&self) -> OutputSettings {
+        OutputSettings {
+            on: true,
+            brightness: self.output_brightness,
+            gamma: Gamma {
+                red: self.output_gamma_red,
+                green: self.output_gamma_green,
+                blue: self.output_gamma_blue,
+            },
+            white_balance: self.output_white_balance,
+            channel_order: self.output_channel_order,
+            power: PowerSettings {
+                milliamps_per_channel: self.output_milliamps_per_channel,
+                idle_milliamps_per_led: self.output_idle_milliamps_per_led,
+                budget_milliamps: self.output_power_budget_milliamps,
+            },
+        }
+    }
+}
+
+/// A canvas declared as `name:led_count:#rrggbb`, e.g. `kitchen:60:#ffffff`.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(try_from = "String")]
+pub struct CanvasConfig {
+    pub name: String,
+    pub led_count: usize,
+    pub default_color: Color,
+}
+
+impl Default for CanvasConfig {
+    fn default() -> Self {
+        Self {
+            name: "default".to_string(),
+            led_count: 150,
+            default_color: Color {
+                red: 255,
+                green: 255,
+                blue: 255,
+            },
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("'{0}' is not a canvas like name:led_count:#rrggbb")]
+pub struct ParseCanvasConfigError(String);
+
+impl FromStr for CanvasConfig {
+    type Err = ParseCanvasConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || ParseCanvasConfigError(s.to_string());
+        let mut parts = s.split(':');
+
+        let name = parts
+            .next()
+            .filter(|name| !name.is_empty())
+            .ok_or_else(error)?;
+        let led_count = parts
+            .next()
+            .and_then(|count| count.parse().ok())
+            .ok_or_else(error)?;
+        let default_color = match parts.next() {
+            Some(color) => color.parse().map_err(|_| error())?,
+            None => CanvasConfig::default().default_color,
+        };
+
+        if parts.next().is_some() {
+            return Err(error());
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            led_count,
+            default_color,
+        })
+    }
+}
+
+impl TryFrom<String> for CanvasConfig {
+    type Error = ParseCanvasConfigError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> { value.parse() }
+}
+
+/// A canvas layout declared as `canvas=kind`, e.g. `matrix=matrix:16x16:serpentine`
+/// or `hall=file:/etc/controlmylights/hall.json`.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(try_from = "String")]
+pub struct LayoutConfig {
+    pub canvas: String,
+    pub kind: LayoutKind,
+}
+
+impl TryFrom<String> for LayoutConfig {
+    type Error = LayoutError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let (canvas, kind) = value
+            .split_once('=')
+            .ok_or_else(|| LayoutError::Parse(value.clone()))?;
+
+        Ok(Self {
+            canvas: canvas.to_string(),
+            kind: kind.parse()?,
+        })
+    }
+}
+
+/// A canvas palette declared as `canvas=mode:#rrggbb[:#rrggbb...]`, where
+/// `mode` is `snap` or `reject`, e.g. `kitchen=snap:#000000:#ff0000:#ffffff`.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(try_from = "String")]
+pub struct PaletteConfig {
+    pub canvas: String,
+    pub palette: Palette,
+}
+
+impl TryFrom<String> for PaletteConfig {
+    type Error = PaletteError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let (canvas, palette) = value
+            .split_once('=')
+            .filter(|(canvas, _)| !canvas.is_empty())
+            .ok_or_else(|| PaletteError::Parse(value.clone()))?;
+
+        Ok(Self {
+            canvas: canvas.to_string(),
+            palette: palette.parse()?,
+        })
+    }
+}
+
+/// An Open Pixel Control channel declared as `canvas=channel`, e.g.
+/// `kitchen=2`.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(try_from = "String")]
+pub struct OpcChannelConfig {
+    pub canvas: String,
+    pub channel: u8,
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("'{0}' is not an OPC channel like canvas=1 (channel 0 is reserved for broadcasts)")]
+pub struct ParseOpcChannelConfigError(String);
+
+impl TryFrom<String> for OpcChannelConfig {
+    type Error = ParseOpcChannelConfigError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let error = || ParseOpcChannelConfigError(value.clone());
+        let (canvas, channel) = value.split_once('=').ok_or_else(error)?;
+        let channel = channel.parse().map_err(|_| error())?;
+
+        if canvas.is_empty() || channel == 0 {
+            return Err(error());
+        }
+
+        Ok(Self {
+            canvas: canvas.to_string(),
+            channel,
+        })
+    }
+}
+
+/// A team declared as `name:#rrggbb[:#rrggbb...]`, the colors its members
+/// may paint with, e.g. `red:#ff0000:#800000`.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(try_from = "String")]
+pub struct TeamConfig {
+    pub name: String,
+    pub palette: Vec<Color>,
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("'{0}' is not a team like name:#rrggbb[:#rrggbb...]")]
+pub struct ParseTeamConfigError(String);
+
+impl TryFrom<String> for TeamConfig {
+    type Error = ParseTeamConfigError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let error = || ParseTeamConfigError(value.clone());
+        let (name, palette) = value.split_once(':').ok_or_else(error)?;
+        let palette = palette
+            .split(':')
+            .map(str::parse)
+            .collect::<Result<Vec<Color>, _>>()
+            .map_err(|_| error())?;
+
+        if name.is_empty() {
+            return Err(error());
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            palette,
+        })
+    }
+}
+
+/// An output declared as `canvas=kind[:target][?fps=N]`, see [`OutputKind`]
+/// for the kinds, e.g. `kitchen=sacn:multicast/1?fps=30`.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(try_from = "String")]
+pub struct OutputConfig {
+    pub canvas: String,
+    pub kind: OutputKind,
+    /// Defaults to `OUTPUT_FPS`.
+    pub fps: Option<u32>,
+}
+
+impl TryFrom<String> for OutputConfig {
+    type Error = ParseOutputKindError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let error = || ParseOutputKindError(value.clone());
+        let (canvas, spec) = value.split_once('=').ok_or_else(error)?;
+        let (kind, fps) = match spec.split_once("?fps=") {
+            Some((kind, fps)) => (kind, Some(fps.parse().map_err(|_| error())?)),
+            None => (spec, None),
+        };
+
+        if canvas.is_empty() || fps == Some(0) {
+            return Err(error());
+        }
+
+        Ok(Self {
+            canvas: canvas.to_string(),
+            kind: kind.parse().map_err(|_| error())?,
+            fps,
+        })
+    }
+}
+
+/// The older way of declaring sACN and Art-Net outputs, as
+/// `canvas@host/universe[/start_channel]`.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(try_from = "String")]
+pub struct DmxOutputConfig {
+    pub canvas: String,
+    pub target: DmxTarget,
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("'{0}' is not an output like canvas@host/universe[/start_channel]")]
+pub struct ParseOutputConfigError(String);
+
+impl TryFrom<String> for DmxOutputConfig {
+    type Error = ParseOutputConfigError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let error = || ParseOutputConfigError(value.clone());
+        let (canvas, target) = value.split_once('@').ok_or_else(error)?;
+
+        Ok(Self {
+            canvas: canvas.to_string(),
+            target: target.parse().map_err(|_| error())?,
+        })
+    }
+}
+
+/// An Art-Net input declared as
+/// `canvas[:first_led[:led_count]]@universe[/start_channel]`, writing the
+/// received channels as RGB into `led_count` leds (the rest of the canvas by
+/// default) starting at `first_led`.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(try_from = "String")]
+pub struct ArtnetInputConfig {
+    pub canvas: String,
+    pub first_led: usize,
+    pub led_count: Option<usize>,
+    pub universe: u16,
+    pub start_channel: u16,
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("'{0}' is not an input like canvas[:first_led[:led_count]]@universe[/start_channel]")]
+pub struct ParseInputConfigError(String);
+
+impl TryFrom<String> for ArtnetInputConfig {
+    type Error = ParseInputConfigError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let error = || ParseInputConfigError(value.clone());
+        let (target, source) = value.split_once('@').ok_or_else(error)?;
+
+        let mut target = target.split(':');
+        let canvas = target
+            .next()
+            .filter(|canvas| !canvas.is_empty())
+            .ok_or_else(error)?;
+        let first_led = match target.next() {
+            Some(first_led) => first_led.parse().map_err(|_| error())?,
+            None => 0,
+        };
+        let led_count = target
+            .next()
+            .map(|led_count| led_count.parse().map_err(|_| error()))
+            .transpose()?;
+
+        let (universe, start_channel) = match source.split_once('/') {
+            Some((universe, channel)) => (universe, channel.parse().map_err(|_| error())?),
+            None => (source, 1),
+        };
+        let universe = universe.parse().map_err(|_| error())?;
+
+        if target.next().is_some() || !(1..=512).contains(&start_channel) {
+            return Err(error());
+        }
+
+        Ok(Self {
+            canvas: canvas.to_string(),
+            first_led,
+            led_count,
+            universe,
+            start_channel,
+        })
+    }
 }
@@ -0,0 +1,123 @@
+use std::{future::Future, sync::Arc, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use serde_inline_default::serde_inline_default;
+use tokio::{
+    sync::Mutex,
+    task::AbortHandle,
+    time::{interval, MissedTickBehavior},
+};
+
+use crate::{
+    font::{self, GLYPH_HEIGHT},
+    layout::Layout,
+    repo::led::LedRepo,
+    types::Color,
+};
+
+/// Runs at most one effect at a time on a canvas, replacing the previous one
+/// when a new one starts.
+#[derive(Clone, Default)]
+pub struct EffectRunner(Arc<Mutex<Option<RunningEffect>>>);
+
+struct RunningEffect {
+    name: &'static str,
+    handle: AbortHandle,
+}
+
+impl EffectRunner {
+    pub fn new() -> Self { Self::default() }
+
+    pub async fn start(
+        &self,
+        name: &'static str,
+        effect: impl Future<Output = ()> + Send + 'static,
+    ) {
+        let mut running = self.0.lock().await;
+
+        if let Some(previous) = running.take() {
+            previous.handle.abort();
+        }
+
+        let handle = tokio::spawn(effect).abort_handle();
+        *running = Some(RunningEffect { name, handle });
+    }
+
+    pub async fn stop(&self) -> Option<&'static str> {
+        let previous = self.0.lock().await.take()?;
+        previous.handle.abort();
+
+        Some(previous.name)
+    }
+
+    /// Name of the effect currently running, if it hasn't finished yet.
+    pub async fn current(&self) -> Option<&'static str> {
+        self.0
+            .lock()
+            .await
+            .as_ref()
+            .filter(|effect| !effect.handle.is_finished())
+            .map(|effect| effect.name)
+    }
+}
+
+#[serde_inline_default]
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ScrollText {
+    pub message: String,
+    #[serde_inline_default(Color { red: 255, green: 255, blue: 255 })]
+    pub color: Color,
+    #[serde_inline_default(Color { red: 0, green: 0, blue: 0 })]
+    pub background: Color,
+    /// Columns scrolled per second.
+    #[serde_inline_default(10.0)]
+    pub speed: f32,
+    /// How many times the message crosses the canvas, 0 to scroll forever.
+    #[serde_inline_default(1)]
+    pub repeat: u32,
+}
+
+/// Scrolls a message from right to left across the canvas, vertically
+/// centered on its layout.
+pub async fn scroll_text(leds: LedRepo, layout: Arc<Layout>, options: ScrollText) {
+    let columns = font::render(&options.message);
+    let (width, height) = layout.size();
+    let top = (height - GLYPH_HEIGHT as i32).max(0) / 2;
+    let text_width = columns.len() as i32;
+
+    let mut ticker = interval(Duration::from_secs_f32(
+        1.0 / options.speed.clamp(0.5, 120.0),
+    ));
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    let mut pass = 0;
+    while options.repeat == 0 || pass < options.repeat {
+        for offset in 0..width + text_width {
+            ticker.tick().await;
+
+            let frame = (0..leds.led_count()).filter_map(|id| {
+                let (x, y) = layout.position(id)?;
+                let column = x - width + offset;
+                let row = y - top;
+
+                let lit = (0..text_width).contains(&column)
+                    && (0..GLYPH_HEIGHT as i32).contains(&row)
+                    && columns[column as usize] & (1 << row) != 0;
+
+                Some((
+                    id,
+                    if lit {
+                        options.color
+                    } else {
+                        options.background
+                    },
+                ))
+            });
+
+            // The frame only contains ids from the canvas' own layout.
+            let _ = leds.set_many(frame).await;
+        }
+
+        pass += 1;
+    }
+}
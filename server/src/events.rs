@@ -0,0 +1,109 @@
+use std::{sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::{sync::broadcast, time::sleep};
+use uuid::Uuid;
+
+use crate::{repo::canvas::Canvas, types::Color};
+
+/// How long led changes are collected before being published as one event.
+const LED_CHANGES_DEBOUNCE: Duration = Duration::from_millis(250);
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    LedsChanged,
+    SceneApplied,
+}
+
+#[derive(Serialize, Clone, Copy, Debug)]
+pub struct ChangedLed {
+    pub id: usize,
+    pub color: Color,
+    pub last_updated: DateTime<Utc>,
+    pub painted_by: Option<Uuid>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AppEvent {
+    LedsChanged {
+        canvas: Arc<str>,
+        generation: usize,
+        leds: Vec<ChangedLed>,
+    },
+    SceneApplied {
+        canvas: Arc<str>,
+        scene: String,
+    },
+}
+
+impl AppEvent {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            AppEvent::LedsChanged { .. } => EventKind::LedsChanged,
+            AppEvent::SceneApplied { .. } => EventKind::SceneApplied,
+        }
+    }
+
+    pub fn canvas(&self) -> &str {
+        match self {
+            AppEvent::LedsChanged { canvas, .. } | AppEvent::SceneApplied { canvas, .. } => canvas,
+        }
+    }
+}
+
+/// Fans application events out to whoever is interested (e.g. webhooks).
+#[derive(Clone)]
+pub struct EventBus(broadcast::Sender<AppEvent>);
+
+impl EventBus {
+    pub fn new(capacity: usize) -> Self { Self(broadcast::Sender::new(capacity)) }
+
+    pub fn publish(&self, event: AppEvent) {
+        // Nobody listening is fine, the event is simply dropped.
+        let _ = self.0.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<AppEvent> { self.0.subscribe() }
+}
+
+/// Publishes a [`AppEvent::LedsChanged`] with the leds that changed every time
+/// the canvas' generation moves, batching bursts of writes together.
+pub async fn publish_led_changes(canvas: Canvas, events: EventBus) {
+    let mut previous = canvas.leds.snapshot().await;
+
+    loop {
+        canvas
+            .leds
+            .wait_for_generation(previous.generation + 1)
+            .await;
+        sleep(LED_CHANGES_DEBOUNCE).await;
+
+        let snapshot = canvas.leds.snapshot().await;
+        let leds: Vec<ChangedLed> = snapshot
+            .leds
+            .iter()
+            .zip(&previous.leds)
+            .enumerate()
+            .filter(|(_, (led, previous))| led.last_updated != previous.last_updated)
+            .map(|(id, (led, _))| ChangedLed {
+                id,
+                color: led.color,
+                last_updated: led.last_updated,
+                painted_by: led.painted_by,
+            })
+            .collect();
+
+        if !leds.is_empty() {
+            events.publish(AppEvent::LedsChanged {
+                canvas: canvas.name.clone(),
+                generation: snapshot.generation,
+                leds,
+            });
+        }
+
+        previous = snapshot;
+    }
+}
@@ -0,0 +1,73 @@
+use crate::{
+    palette::{apply_palette, PaletteError},
+    repo::{
+        canvas::Canvas,
+        session::Session,
+        team::{TeamError, TeamRepo},
+        voting::{VotingError, VotingRepo},
+    },
+    types::Color,
+};
+
+/// Why a write was refused, for writers that don't report it to a client.
+#[derive(thiserror::Error, Debug)]
+pub enum WriteError {
+    #[error(transparent)]
+    Team(#[from] TeamError),
+    #[error(transparent)]
+    Voting(#[from] VotingError),
+    #[error(transparent)]
+    Palette(#[from] PaletteError),
+}
+
+/// The checks every user-facing write to a canvas goes through, whether it
+/// comes from the web API, WLED apps, MQTT or a pixel protocol. Art-Net input
+/// comes from the operator's lighting desk and isn't checked.
+#[derive(Clone)]
+pub struct WriteGate {
+    teams: TeamRepo,
+    voting: VotingRepo,
+}
+
+impl WriteGate {
+    pub fn new(teams: TeamRepo, voting: VotingRepo) -> Self { Self { teams, voting } }
+
+    /// Checks a write of `color` to a single led of `canvas`, returning the
+    /// color to paint instead.
+    pub fn check_led<E>(
+        &self,
+        canvas: &Canvas,
+        session: Option<&Session>,
+        color: Color,
+    ) -> Result<Color, E>
+    where
+        E: From<TeamError> + From<VotingError> + From<PaletteError>,
+    {
+        self.voting.check_write(&canvas.name)?;
+        let color = apply_palette(canvas.palette.as_deref(), color)?;
+        self.teams.check_write(&canvas.name, session, color)?;
+
+        Ok(color)
+    }
+
+    /// Checks a write of `colors` to more than a single led of `canvas`, e.g.
+    /// a scene, an image, a fill or a frame of pixels, and swaps them for the
+    /// colors to paint instead. A single color missing from a rejecting
+    /// palette refuses the whole write.
+    pub fn check_bulk<'a, E>(
+        &self,
+        canvas: &Canvas,
+        colors: impl IntoIterator<Item = &'a mut Color>,
+    ) -> Result<(), E>
+    where
+        E: From<TeamError> + From<VotingError> + From<PaletteError>,
+    {
+        self.voting.check_write(&canvas.name)?;
+        self.teams.check_bulk_write(&canvas.name)?;
+        for color in colors {
+            *color = apply_palette(canvas.palette.as_deref(), *color)?;
+        }
+
+        Ok(())
+    }
+}
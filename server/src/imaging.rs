@@ -0,0 +1,115 @@
+use image::{imageops::FilterType, DynamicImage, ImageError, Rgb, RgbImage};
+use serde::Deserialize;
+
+use crate::{layout::Layout, types::Color};
+
+/// How an image with a different aspect ratio than the canvas is scaled.
+#[derive(Deserialize, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Fit {
+    /// Scale to fit inside the canvas, leaving black bars.
+    #[default]
+    Contain,
+    /// Scale to cover the canvas, cropping the overflow.
+    Cover,
+    /// Scale each axis independently, distorting the image.
+    Stretch,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ImageOptions {
+    pub fit: Fit,
+    /// Number of levels each channel is quantized to, 256 keeps full precision.
+    pub levels: u16,
+    /// Spread the quantization error with Floyd-Steinberg dithering.
+    pub dither: bool,
+}
+
+/// Decodes a PNG/JPEG and maps it onto the canvas layout, returning one color
+/// per LED id.
+pub fn map_image(
+    bytes: &[u8],
+    layout: &Layout,
+    led_count: usize,
+    options: ImageOptions,
+) -> Result<Vec<Color>, ImageError> {
+    let (width, height) = layout.size();
+    let (width, height) = (width.max(1) as u32, height.max(1) as u32);
+
+    let image = image::load_from_memory(bytes)?;
+    let mut grid = scale(&image, width, height, options.fit);
+    quantize(&mut grid, options.levels.clamp(2, 256), options.dither);
+
+    Ok((0..led_count)
+        .map(|id| {
+            let (x, y) = layout.position(id).unwrap_or_default();
+            let Rgb([red, green, blue]) = grid
+                .get_pixel_checked(x.max(0) as u32, y.max(0) as u32)
+                .copied()
+                .unwrap_or(Rgb([0, 0, 0]));
+            Color { red, green, blue }
+        })
+        .collect())
+}
+
+fn scale(image: &DynamicImage, width: u32, height: u32, fit: Fit) -> RgbImage {
+    match fit {
+        Fit::Stretch => image
+            .resize_exact(width, height, FilterType::Triangle)
+            .to_rgb8(),
+        Fit::Cover => image
+            .resize_to_fill(width, height, FilterType::Triangle)
+            .to_rgb8(),
+        Fit::Contain => {
+            let scaled = image.resize(width, height, FilterType::Triangle).to_rgb8();
+            let mut grid = RgbImage::new(width, height);
+            image::imageops::overlay(
+                &mut grid,
+                &scaled,
+                ((width - scaled.width()) / 2).into(),
+                ((height - scaled.height()) / 2).into(),
+            );
+            grid
+        }
+    }
+}
+
+fn quantize(grid: &mut RgbImage, levels: u16, dither: bool) {
+    if levels >= 256 {
+        return;
+    }
+
+    let step = 255.0 / f32::from(levels - 1);
+    let (width, height) = grid.dimensions();
+    let mut errors = vec![[0.0f32; 3]; (width * height) as usize];
+
+    for y in 0..height {
+        for x in 0..width {
+            let index = (y * width + x) as usize;
+            let pixel = grid.get_pixel_mut(x, y);
+
+            for channel in 0..3 {
+                let value = f32::from(pixel[channel]) + errors[index][channel];
+                let quantized = ((value / step).round() * step).clamp(0.0, 255.0);
+                pixel[channel] = quantized as u8;
+
+                if !dither {
+                    continue;
+                }
+
+                let error = value - quantized;
+                let mut spread = |dx: i64, dy: u32, weight: f32| {
+                    let (nx, ny) = (i64::from(x) + dx, y + dy);
+                    if (0..i64::from(width)).contains(&nx) && ny < height {
+                        errors[(ny * width) as usize + nx as usize][channel] +=
+                            error * weight / 16.0;
+                    }
+                };
+                spread(1, 0, 7.0);
+                spread(-1, 1, 3.0);
+                spread(0, 1, 5.0);
+                spread(1, 1, 1.0);
+            }
+        }
+    }
+}
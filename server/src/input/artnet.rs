@@ -0,0 +1,153 @@
+use std::{ops::Range, time::Duration};
+
+use tokio::net::UdpSocket;
+
+use crate::{
+    config::ArtnetInputConfig,
+    output::{
+        artnet::{ARTNET_ID, MAX_PORT_ADDRESS, OP_DMX},
+        UNIVERSE_SIZE,
+    },
+    repo::canvas::Canvas,
+    types::Color,
+};
+
+const HEADER_LENGTH: usize = 18;
+const PIXELS_PER_UNIVERSE: usize = UNIVERSE_SIZE / 3;
+
+/// An [`ArtnetInputConfig`] resolved against its canvas.
+pub struct ArtnetInput {
+    pub canvas: Canvas,
+    pub leds: Range<usize>,
+    pub universe: u16,
+    pub start_channel: u16,
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("Art-Net input starts at led {first_led} but canvas '{canvas}' only has {led_count}")]
+pub struct ArtnetInputError {
+    canvas: String,
+    first_led: usize,
+    led_count: usize,
+}
+
+impl ArtnetInput {
+    pub fn new(canvas: Canvas, config: &ArtnetInputConfig) -> Result<Self, ArtnetInputError> {
+        let led_count = canvas.leds.led_count();
+        if config.first_led >= led_count {
+            return Err(ArtnetInputError {
+                canvas: config.canvas.clone(),
+                first_led: config.first_led,
+                led_count,
+            });
+        }
+
+        let available = led_count - config.first_led;
+        let count = config.led_count.unwrap_or(available).min(available);
+
+        Ok(Self {
+            canvas,
+            leds: config.first_led..config.first_led + count,
+            universe: config.universe & MAX_PORT_ADDRESS,
+            start_channel: config.start_channel,
+        })
+    }
+
+    /// Maps the channels of `universe` to leds, laid out the same way
+    /// [`crate::output::split_universes`] spreads them, so without splitting
+    /// a pixel between two universes.
+    fn colors(&self, universe: u16, data: &[u8]) -> Vec<(usize, Color)> {
+        let Some(index) = universe.checked_sub(self.universe) else {
+            return Vec::new();
+        };
+        let first_offset = usize::from(self.start_channel) - 1;
+        let first_pixels = (UNIVERSE_SIZE - first_offset) / 3;
+        let (led_offset, channel_offset) = match index {
+            0 => (0, first_offset),
+            index => (
+                first_pixels + (usize::from(index) - 1) * PIXELS_PER_UNIVERSE,
+                0,
+            ),
+        };
+
+        data.get(channel_offset..)
+            .unwrap_or_default()
+            .chunks_exact(3)
+            .enumerate()
+            .map(|(pixel, rgb)| (self.leds.start + led_offset + pixel, rgb))
+            .take_while(|(id, _)| self.leds.contains(id))
+            .map(|(id, rgb)| {
+                (
+                    id,
+                    Color {
+                        red: rgb[0],
+                        green: rgb[1],
+                        blue: rgb[2],
+                    },
+                )
+            })
+            .collect()
+    }
+}
+
+/// Listens for ArtDmx packets and writes them into the mapped leds. Every
+/// packet holds the whole mapped range for `hold`, so a lighting desk keeps
+/// control over web writes for as long as it sends.
+///
+/// The desk is the operator's, so its writes skip the [`crate::gate::WriteGate`]
+/// users go through.
+pub async fn run_artnet_input(bind_address: String, inputs: Vec<ArtnetInput>, hold: Duration) {
+    let socket = match UdpSocket::bind(&bind_address).await {
+        Ok(socket) => socket,
+        Err(err) => {
+            tracing::error!("Failed to bind Art-Net input to {bind_address}: {err}");
+            return;
+        }
+    };
+
+    tracing::info!("Listening for Art-Net on {bind_address}");
+
+    let mut buffer = [0u8; HEADER_LENGTH + UNIVERSE_SIZE];
+    loop {
+        let (length, source) = match socket.recv_from(&mut buffer).await {
+            Ok(received) => received,
+            Err(err) => {
+                tracing::warn!("Failed to receive Art-Net packet: {err}");
+                continue;
+            }
+        };
+
+        let Some((universe, data)) = parse_dmx_packet(&buffer[..length]) else {
+            continue;
+        };
+
+        for input in &inputs {
+            let colors = input.colors(universe, data);
+            if colors.is_empty() {
+                continue;
+            }
+
+            tracing::trace!("Art-Net universe {universe} from {source}");
+            input.canvas.leds.hold(input.leds.clone(), hold);
+            // Ids are clamped to the canvas in `ArtnetInput::new`.
+            let _ = input.canvas.leds.override_many(colors).await;
+        }
+    }
+}
+
+/// Returns the port address and channel data of an ArtDmx packet, ignoring
+/// any other Art-Net operation.
+fn parse_dmx_packet(packet: &[u8]) -> Option<(u16, &[u8])> {
+    if packet.len() < HEADER_LENGTH
+        || &packet[..8] != ARTNET_ID
+        || u16::from_le_bytes([packet[8], packet[9]]) != OP_DMX
+    {
+        return None;
+    }
+
+    let universe = u16::from_le_bytes([packet[14], packet[15]]) & MAX_PORT_ADDRESS;
+    let length = usize::from(u16::from_be_bytes([packet[16], packet[17]]));
+    let data = &packet[HEADER_LENGTH..];
+
+    Some((universe, &data[..length.min(data.len())]))
+}
@@ -0,0 +1,229 @@
+use std::time::Duration;
+
+use tokio::net::UdpSocket;
+
+use crate::{
+    gate::{WriteError, WriteGate},
+    repo::canvas::Canvas,
+    types::Color,
+};
+
+const HEADER_LENGTH: usize = 10;
+/// Senders may append a timecode to the header.
+const TIMECODE_LENGTH: usize = 4;
+const MAX_PACKET_LENGTH: usize = 1500;
+
+const VERSION_MASK: u8 = 0xc0;
+const VERSION_1: u8 = 0x40;
+const FLAG_TIMECODE: u8 = 0x10;
+const FLAG_QUERY: u8 = 0x02;
+const FLAG_PUSH: u8 = 0x01;
+
+/// Type bits for RGBW pixels, everything else is treated as RGB.
+const TYPE_RGBW: u8 = 3;
+/// Ids from here on address control, config and status instead of pixels.
+const FIRST_CONTROL_ID: u8 = 246;
+const ID_ALL: u8 = 255;
+
+/// Listens for DDP pixel packets and writes them into the canvas. Pixels are
+/// buffered until a packet with the push flag arrives, unless the sender
+/// never sets it. Every write holds the whole canvas for `hold`, like WLED's
+/// realtime mode.
+pub async fn run_ddp_input(bind_address: String, canvas: Canvas, gate: WriteGate, hold: Duration) {
+    let socket = match UdpSocket::bind(&bind_address).await {
+        Ok(socket) => socket,
+        Err(err) => {
+            tracing::error!("Failed to bind DDP input to {bind_address}: {err}");
+            return;
+        }
+    };
+
+    tracing::info!("Listening for DDP on {bind_address}");
+
+    let led_count = canvas.leds.led_count();
+    // Pixels waiting for a push, later packets overwrite earlier ones.
+    let mut pending: Vec<Option<Color>> = vec![None; led_count];
+    let mut seen_push = false;
+    let mut buffer = [0u8; MAX_PACKET_LENGTH];
+
+    loop {
+        let length = match socket.recv_from(&mut buffer).await {
+            Ok((length, _)) => length,
+            Err(err) => {
+                tracing::warn!("Failed to receive DDP packet: {err}");
+                continue;
+            }
+        };
+
+        let Some(packet) = DdpPacket::parse(&buffer[..length]) else {
+            continue;
+        };
+
+        for (id, color) in packet.colors().take_while(|(id, _)| *id < led_count) {
+            pending[id] = Some(color);
+        }
+
+        seen_push |= packet.push;
+        if packet.push || !seen_push {
+            if let Err(err) = gate.check_bulk::<WriteError>(&canvas, pending.iter_mut().flatten()) {
+                tracing::debug!("Refused DDP pixels: {err}");
+                pending.fill(None);
+                continue;
+            }
+
+            canvas.leds.hold(0..led_count, hold);
+            let colors = pending
+                .iter_mut()
+                .enumerate()
+                .filter_map(|(id, color)| Some((id, color.take()?)));
+            // Ids past the canvas are cut off above.
+            let _ = canvas.leds.override_many(colors).await;
+        }
+    }
+}
+
+struct DdpPacket<'a> {
+    push: bool,
+    channels_per_pixel: usize,
+    /// In channels, not pixels.
+    offset: usize,
+    data: &'a [u8],
+}
+
+impl<'a> DdpPacket<'a> {
+    fn parse(packet: &'a [u8]) -> Option<Self> {
+        let header = packet.get(..HEADER_LENGTH)?;
+        let flags = header[0];
+        let data_type = header[2];
+        let id = header[3];
+
+        if flags & VERSION_MASK != VERSION_1
+            || flags & FLAG_QUERY != 0
+            || (FIRST_CONTROL_ID..ID_ALL).contains(&id)
+        {
+            return None;
+        }
+
+        let offset = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize;
+        let length = usize::from(u16::from_be_bytes([header[8], header[9]]));
+        let data_start = if flags & FLAG_TIMECODE != 0 {
+            HEADER_LENGTH + TIMECODE_LENGTH
+        } else {
+            HEADER_LENGTH
+        };
+        let data = packet.get(data_start..)?;
+
+        Some(Self {
+            push: flags & FLAG_PUSH != 0,
+            channels_per_pixel: if (data_type >> 3) & 0x07 == TYPE_RGBW {
+                4
+            } else {
+                3
+            },
+            offset,
+            data: &data[..length.min(data.len())],
+        })
+    }
+
+    /// Colors by led id. White is folded into the color channels for RGBW
+    /// pixels.
+    fn colors(&self) -> impl Iterator<Item = (usize, Color)> + '_ {
+        let first_id = self.offset / self.channels_per_pixel;
+
+        self.data
+            .chunks_exact(self.channels_per_pixel)
+            .enumerate()
+            .map(move |(index, channels)| {
+                let white = channels.get(3).copied().unwrap_or(0);
+
+                (
+                    first_id + index,
+                    Color {
+                        red: channels[0].saturating_add(white),
+                        green: channels[1].saturating_add(white),
+                        blue: channels[2].saturating_add(white),
+                    },
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(flags: u8, data_type: u8, id: u8, offset: u32, length: u16) -> Vec<u8> {
+        let mut packet = vec![flags, 0, data_type, id];
+        packet.extend_from_slice(&offset.to_be_bytes());
+        packet.extend_from_slice(&length.to_be_bytes());
+        packet
+    }
+
+    fn color(red: u8, green: u8, blue: u8) -> Color { Color { red, green, blue } }
+
+    #[test]
+    fn parses_rgb_pixels() {
+        let mut bytes = header(VERSION_1 | FLAG_PUSH, 0, 1, 6, 6);
+        bytes.extend_from_slice(&[1, 2, 3, 4, 5, 6]);
+
+        let packet = DdpPacket::parse(&bytes).unwrap();
+
+        assert!(packet.push);
+        assert_eq!(
+            packet.colors().collect::<Vec<_>>(),
+            [(2, color(1, 2, 3)), (3, color(4, 5, 6))]
+        );
+    }
+
+    #[test]
+    fn folds_white_into_rgbw_pixels() {
+        let mut bytes = header(VERSION_1, TYPE_RGBW << 3, 1, 0, 4);
+        bytes.extend_from_slice(&[10, 250, 0, 20]);
+
+        let packet = DdpPacket::parse(&bytes).unwrap();
+
+        assert!(!packet.push);
+        assert_eq!(
+            packet.colors().collect::<Vec<_>>(),
+            [(0, color(30, 255, 20))]
+        );
+    }
+
+    #[test]
+    fn skips_the_timecode() {
+        let mut bytes = header(VERSION_1 | FLAG_TIMECODE, 0, 1, 0, 3);
+        bytes.extend_from_slice(&[0xff; TIMECODE_LENGTH]);
+        bytes.extend_from_slice(&[7, 8, 9]);
+
+        let packet = DdpPacket::parse(&bytes).unwrap();
+
+        assert_eq!(packet.colors().collect::<Vec<_>>(), [(0, color(7, 8, 9))]);
+    }
+
+    #[test]
+    fn cuts_data_at_the_declared_length() {
+        let mut bytes = header(VERSION_1, 0, 1, 0, 3);
+        bytes.extend_from_slice(&[1, 2, 3, 4, 5, 6]);
+
+        let packet = DdpPacket::parse(&bytes).unwrap();
+
+        assert_eq!(packet.colors().count(), 1);
+    }
+
+    #[test]
+    fn rejects_non_pixel_packets() {
+        let data = [1, 2, 3];
+        let with_data = |mut packet: Vec<u8>| {
+            packet.extend_from_slice(&data);
+            packet
+        };
+
+        assert!(DdpPacket::parse(&with_data(header(0x80, 0, 1, 0, 3))).is_none());
+        assert!(DdpPacket::parse(&with_data(header(VERSION_1 | FLAG_QUERY, 0, 1, 0, 3))).is_none());
+        assert!(
+            DdpPacket::parse(&with_data(header(VERSION_1, 0, FIRST_CONTROL_ID, 0, 3))).is_none()
+        );
+        assert!(DdpPacket::parse(&with_data(header(VERSION_1, 0, ID_ALL, 0, 3))).is_some());
+        assert!(DdpPacket::parse(&[VERSION_1, 0, 0]).is_none());
+    }
+}
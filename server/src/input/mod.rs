@@ -0,0 +1,5 @@
+//! Sources writing into canvases from outside the web API.
+
+pub mod artnet;
+pub mod ddp;
+pub mod opc;
@@ -0,0 +1,97 @@
+use tokio::{
+    io::{AsyncReadExt, BufReader},
+    net::{TcpListener, TcpStream},
+};
+
+use crate::{
+    gate::{WriteError, WriteGate},
+    repo::canvas::Canvas,
+    types::Color,
+};
+
+const HEADER_LENGTH: usize = 4;
+/// Messages on this channel go to every mapped canvas.
+const BROADCAST_CHANNEL: u8 = 0;
+const COMMAND_SET_PIXELS: u8 = 0;
+
+/// Accepts Open Pixel Control clients and writes their "set pixel colors"
+/// messages into the canvas mapped to the message's channel. Writes go
+/// through the same path and checks as the web API, so held leds are left
+/// alone.
+pub async fn run_opc_server(bind_address: String, channels: Vec<(u8, Canvas)>, gate: WriteGate) {
+    let listener = match TcpListener::bind(&bind_address).await {
+        Ok(listener) => listener,
+        Err(err) => {
+            tracing::error!("Failed to bind OPC server to {bind_address}: {err}");
+            return;
+        }
+    };
+
+    tracing::info!("Listening for OPC clients on {bind_address}");
+
+    loop {
+        match listener.accept().await {
+            Ok((stream, address)) => {
+                tracing::info!("OPC client {address} connected");
+                tokio::spawn(handle_client(stream, channels.clone(), gate.clone()));
+            }
+            Err(err) => tracing::warn!("Failed to accept OPC client: {err}"),
+        }
+    }
+}
+
+async fn handle_client(stream: TcpStream, channels: Vec<(u8, Canvas)>, gate: WriteGate) {
+    let mut reader = BufReader::new(stream);
+    let mut header = [0u8; HEADER_LENGTH];
+    let mut data = Vec::new();
+
+    loop {
+        if let Err(err) = reader.read_exact(&mut header).await {
+            tracing::info!("OPC client disconnected: {err}");
+            return;
+        }
+
+        let [channel, command, high, low] = header;
+        data.resize(usize::from(u16::from_be_bytes([high, low])), 0);
+
+        if let Err(err) = reader.read_exact(&mut data).await {
+            tracing::info!("OPC client disconnected: {err}");
+            return;
+        }
+
+        // System exclusive and unknown commands are ignored, as the spec asks.
+        if command != COMMAND_SET_PIXELS {
+            continue;
+        }
+
+        for (_, canvas) in channels
+            .iter()
+            .filter(|(mapped, _)| channel == BROADCAST_CHANNEL || *mapped == channel)
+        {
+            let mut colors: Vec<(usize, Color)> = data
+                .chunks_exact(3)
+                .take(canvas.leds.led_count())
+                .enumerate()
+                .map(|(id, rgb)| {
+                    (
+                        id,
+                        Color {
+                            red: rgb[0],
+                            green: rgb[1],
+                            blue: rgb[2],
+                        },
+                    )
+                })
+                .collect();
+            let checked =
+                gate.check_bulk::<WriteError>(canvas, colors.iter_mut().map(|(_, color)| color));
+            if let Err(err) = checked {
+                tracing::debug!("Refused OPC pixels: {err}");
+                continue;
+            }
+
+            // Pixels past the end of the canvas are cut off above.
+            let _ = canvas.leds.set_many(colors).await;
+        }
+    }
+}
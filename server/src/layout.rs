@@ -0,0 +1,304 @@
+use std::{collections::HashMap, fs, path::PathBuf, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+const MAX_EXTENT: i32 = 1 << 16;
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct Point {
+    pub x: f32,
+    pub y: f32,
+}
+
+/// A cell of the integer grid LEDs are addressed by.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cell {
+    pub x: i32,
+    pub y: i32,
+}
+
+/// How the LEDs of a canvas are physically arranged.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LayoutKind {
+    /// A single row, LED `n` at `(n, 0)`.
+    Linear,
+    /// Rows of `width` LEDs wired row after row, starting at the top left.
+    /// With `serpentine` wiring every other row runs right to left.
+    Matrix {
+        width: usize,
+        height: usize,
+        serpentine: bool,
+    },
+    /// Arbitrary positions, one per LED id.
+    Points { points: Vec<Point> },
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum LayoutError {
+    #[error("'{0}' is not a layout like linear, matrix:16x16[:serpentine] or file:<path>")]
+    Parse(String),
+    #[error("Failed to read layout file {0}: {1}")]
+    Read(PathBuf, String),
+    #[error("Layout covers {layout} leds but the canvas has {canvas}")]
+    LedCountMismatch { layout: usize, canvas: usize },
+}
+
+impl FromStr for LayoutKind {
+    type Err = LayoutError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || LayoutError::Parse(s.to_string());
+
+        match s.split_once(':') {
+            None if s == "linear" => Ok(LayoutKind::Linear),
+            Some(("matrix", spec)) => {
+                let (size, wiring) = match spec.split_once(':') {
+                    Some((size, wiring)) => (size, Some(wiring)),
+                    None => (spec, None),
+                };
+                let (width, height) = size.split_once('x').ok_or_else(error)?;
+
+                Ok(LayoutKind::Matrix {
+                    width: width.parse().map_err(|_| error())?,
+                    height: height.parse().map_err(|_| error())?,
+                    serpentine: match wiring {
+                        None | Some("progressive") => false,
+                        Some("serpentine") => true,
+                        Some(_) => return Err(error()),
+                    },
+                })
+            }
+            Some(("file", path)) => {
+                let path = PathBuf::from(path);
+                let contents = fs::read_to_string(&path)
+                    .map_err(|err| LayoutError::Read(path.clone(), err.to_string()))?;
+                let points = serde_json::from_str(&contents)
+                    .map_err(|err| LayoutError::Read(path, err.to_string()))?;
+
+                Ok(LayoutKind::Points { points })
+            }
+            _ => Err(error()),
+        }
+    }
+}
+
+/// A [`LayoutKind`] resolved for a canvas, able to map 2D coordinates to LED
+/// ids and back.
+#[derive(Clone, Debug)]
+pub struct Layout {
+    kind: LayoutKind,
+    positions: Vec<(i32, i32)>,
+    ids: HashMap<(i32, i32), usize>,
+}
+
+#[derive(Serialize)]
+pub struct LayoutDescription<'a> {
+    pub kind: &'a LayoutKind,
+    pub width: i32,
+    pub height: i32,
+    /// Grid cell of every LED, indexed by id.
+    pub leds: Vec<Cell>,
+}
+
+impl Layout {
+    pub fn new(kind: LayoutKind, led_count: usize) -> Result<Self, LayoutError> {
+        let positions: Vec<(i32, i32)> = match &kind {
+            LayoutKind::Linear => (0..led_count as i32).map(|x| (x, 0)).collect(),
+            LayoutKind::Matrix {
+                width,
+                height,
+                serpentine,
+            } => (0..*height as i32)
+                .flat_map(|y| {
+                    let width = *width as i32;
+                    let reversed = *serpentine && y % 2 == 1;
+                    (0..width).map(move |x| (if reversed { width - 1 - x } else { x }, y))
+                })
+                .collect(),
+            LayoutKind::Points { points } => points
+                .iter()
+                .map(|point| (point.x.round() as i32, point.y.round() as i32))
+                .collect(),
+        };
+
+        if positions.len() != led_count {
+            return Err(LayoutError::LedCountMismatch {
+                layout: positions.len(),
+                canvas: led_count,
+            });
+        }
+
+        // When several points round to the same cell the lowest id wins.
+        let mut ids = HashMap::new();
+        for (id, position) in positions.iter().enumerate() {
+            ids.entry(*position).or_insert(id);
+        }
+
+        Ok(Self {
+            kind,
+            positions,
+            ids,
+        })
+    }
+
+    pub fn linear(led_count: usize) -> Self {
+        Self::new(LayoutKind::Linear, led_count).expect("linear layouts always match")
+    }
+
+    pub fn id_at(&self, x: i32, y: i32) -> Option<usize> { self.ids.get(&(x, y)).copied() }
+
+    pub fn position(&self, id: usize) -> Option<(i32, i32)> { self.positions.get(id).copied() }
+
+    /// Size of the grid covering every LED, counted from the origin.
+    pub fn size(&self) -> (i32, i32) {
+        self.positions
+            .iter()
+            .fold((0, 0), |(width, height), (x, y)| {
+                (width.max(x + 1), height.max(y + 1))
+            })
+    }
+
+    pub fn describe(&self) -> LayoutDescription<'_> {
+        let (width, height) = self.size();
+
+        LayoutDescription {
+            kind: &self.kind,
+            width,
+            height,
+            leds: self.positions.iter().map(|&(x, y)| Cell { x, y }).collect(),
+        }
+    }
+
+    /// Ids of the LEDs inside the rectangle, skipping cells without a LED.
+    pub fn rect(&self, x: i32, y: i32, width: i32, height: i32) -> Vec<usize> {
+        let (grid_width, grid_height) = self.size();
+        let (x_start, x_end) = (x.max(0), x.saturating_add(width).min(grid_width));
+        let (y_start, y_end) = (y.max(0), y.saturating_add(height).min(grid_height));
+
+        (y_start..y_end)
+            .flat_map(|y| (x_start..x_end).map(move |x| (x, y)))
+            .filter_map(|(x, y)| self.id_at(x, y))
+            .collect()
+    }
+
+    /// Ids of the LEDs on the line between two cells (Bresenham).
+    pub fn line(&self, (x0, y0): (i32, i32), (x1, y1): (i32, i32)) -> Vec<usize> {
+        // Keeps absurd endpoints from turning into millions of steps.
+        let clamp = |x: i32, y: i32| {
+            (
+                x.clamp(-MAX_EXTENT, MAX_EXTENT),
+                y.clamp(-MAX_EXTENT, MAX_EXTENT),
+            )
+        };
+        let ((x0, y0), (x1, y1)) = (clamp(x0, y0), clamp(x1, y1));
+
+        let dx = (x1 - x0).abs();
+        let dy = -(y1 - y0).abs();
+        let step_x = if x0 < x1 { 1 } else { -1 };
+        let step_y = if y0 < y1 { 1 } else { -1 };
+
+        let (mut x, mut y) = (x0, y0);
+        let mut error = dx + dy;
+        let mut ids = Vec::new();
+
+        loop {
+            ids.extend(self.id_at(x, y));
+
+            if x == x1 && y == y1 {
+                break;
+            }
+
+            let doubled = 2 * error;
+            if doubled >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if doubled <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+
+        ids
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matrix(width: usize, height: usize, serpentine: bool) -> Layout {
+        let kind = LayoutKind::Matrix {
+            width,
+            height,
+            serpentine,
+        };
+        Layout::new(kind, width * height).unwrap()
+    }
+
+    #[test]
+    fn serpentine_flips_every_other_row() {
+        let layout = matrix(3, 3, true);
+
+        assert_eq!(layout.position(0), Some((0, 0)));
+        assert_eq!(layout.position(2), Some((2, 0)));
+        assert_eq!(layout.position(3), Some((2, 1)));
+        assert_eq!(layout.position(5), Some((0, 1)));
+        assert_eq!(layout.position(6), Some((0, 2)));
+        assert_eq!(layout.id_at(0, 1), Some(5));
+    }
+
+    #[test]
+    fn progressive_rows_run_left_to_right() {
+        let layout = matrix(3, 2, false);
+
+        assert_eq!(layout.position(3), Some((0, 1)));
+        assert_eq!(layout.id_at(2, 1), Some(5));
+    }
+
+    #[test]
+    fn led_count_must_match() {
+        let kind = LayoutKind::Matrix {
+            width: 4,
+            height: 4,
+            serpentine: false,
+        };
+
+        assert!(matches!(
+            Layout::new(kind, 15),
+            Err(LayoutError::LedCountMismatch {
+                layout: 16,
+                canvas: 15
+            })
+        ));
+    }
+
+    #[test]
+    fn rect_is_clipped_to_the_grid() {
+        let layout = matrix(4, 4, false);
+
+        assert_eq!(layout.rect(-2, -2, 3, 3), vec![0]);
+        assert_eq!(layout.rect(3, 3, 10, 10), vec![15]);
+        assert_eq!(layout.rect(1, 1, 2, 2), vec![5, 6, 9, 10]);
+        assert!(layout.rect(10, 10, 2, 2).is_empty());
+        assert_eq!(layout.rect(0, 0, i32::MAX, 1), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn line_includes_both_endpoints() {
+        let layout = matrix(4, 4, false);
+
+        assert_eq!(layout.line((0, 0), (3, 0)), vec![0, 1, 2, 3]);
+        assert_eq!(layout.line((3, 3), (0, 0)), vec![15, 10, 5, 0]);
+        assert_eq!(layout.line((1, 2), (1, 2)), vec![9]);
+    }
+
+    #[test]
+    fn line_skips_cells_outside_the_grid() {
+        let layout = Layout::linear(4);
+
+        assert_eq!(layout.line((-2, 0), (5, 0)), vec![0, 1, 2, 3]);
+        assert_eq!(layout.line((i32::MIN, 0), (1, 0)), vec![0, 1]);
+    }
+}
@@ -1,8 +1,25 @@
 pub mod config;
+pub mod effects;
+pub mod events;
+pub mod font;
+pub mod gate;
+pub mod imaging;
+pub mod input;
 pub mod ipinfo_lookup;
+pub mod layout;
+pub mod metrics;
+pub mod mqtt;
+pub mod output;
+pub mod palette;
+pub mod pipeline;
 pub mod repo;
 pub mod routers;
 pub mod routes;
+pub mod scheduler;
+pub mod solar;
 pub mod state;
+pub mod teams;
 pub mod tracing;
 pub mod types;
+pub mod voting;
+pub mod webhooks;
@@ -1,27 +1,47 @@
-use std::{net::SocketAddr, sync::Arc};
+use std::{net::SocketAddr, sync::Arc, time::Duration};
 
-use axum::{http::request::Request, routing::get, Extension, Router};
+use axum::{
+    http::{header::ETAG, request::Request, HeaderName},
+    routing::get,
+    Extension, Router,
+};
 use controlmylights::{
     config::Config,
+    events::{publish_led_changes, EventBus},
+    gate::WriteGate,
+    input::{
+        artnet::{run_artnet_input, ArtnetInput},
+        ddp::run_ddp_input,
+        opc::run_opc_server,
+    },
     ipinfo_lookup::ipinfo_lookup,
-    repo::led::LedRepo,
-    routers::api,
+    mqtt::run_mqtt,
+    output::{create_sink, run_sink, OutputKind},
+    repo::{
+        canvas::CanvasRepo, client::ClientRepo, device::DeviceRepo, output::OutputRepo,
+        schedule::ScheduleRepo, session::SessionRepo, team::TeamRepo, voting::VotingRepo,
+        webhook::WebhookRepo,
+    },
+    routers::{api, wled},
     routes::light_bulb_generated::get_randomly_generated_light_bulb_svg,
+    scheduler::run_scheduler,
     state::AppState,
-    tracing::{setup_tracing, TracingConfig},
-    types::Color,
+    teams::run_team_scoring,
+    tracing::{make_request_span, setup_tracing, TracingConfig},
+    voting::run_voting_rounds,
+    webhooks::run_webhooks,
 };
 use ipinfo::{IpInfo, IpInfoConfig};
 use serde_envfile::from_env;
 use tokio::sync::Mutex;
 use tower_http::{
-    cors::{AllowMethods, AllowOrigin, CorsLayer},
+    cors::{AllowHeaders, AllowMethods, AllowOrigin, CorsLayer},
     services::{ServeDir, ServeFile},
-    trace::{DefaultMakeSpan, DefaultOnResponse, TraceLayer},
+    trace::{DefaultOnResponse, TraceLayer},
 };
 use tracing::Span;
 
-const LED_COUNT: usize = 150;
+const MAX_VOTING_ROUND_SECONDS: u64 = 24 * 60 * 60;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -34,8 +54,14 @@ async fn main() -> anyhow::Result<()> {
         otlp_authorization_header: config.otlp_authorization_header.as_deref(),
     })?;
 
+    let coordinates = config.coordinates();
+    if coordinates.is_none() {
+        tracing::warn!("LATITUDE/LONGITUDE not provided, solar schedules will be disabled");
+    }
+
     let ipinfo = config
         .ipinfo_token
+        .as_ref()
         .map(|token| {
             let ipinfo_config = IpInfoConfig {
                 token: Some(token.to_string()),
@@ -49,23 +75,187 @@ async fn main() -> anyhow::Result<()> {
         tracing::warn!("IPInfo token not provided, IP lookup will be disabled");
     }
 
-    let leds = LedRepo::new(vec![
-        Color {
-            red: 255,
-            green: 255,
-            blue: 255,
+    let canvases = CanvasRepo::new(
+        &config.canvases,
+        &config.layouts,
+        &config.palettes,
+        &config.default_canvas,
+    )?;
+
+    let schedules = ScheduleRepo::new();
+    tokio::spawn(run_scheduler(
+        schedules.clone(),
+        canvases.clone(),
+        coordinates,
+    ));
+
+    let events = EventBus::new(1024);
+    for canvas in canvases.iter() {
+        tokio::spawn(publish_led_changes(canvas.clone(), events.clone()));
+    }
+
+    let output = OutputRepo::new(config.output_settings());
+
+    let outputs = config.all_outputs();
+    for output_config in &outputs {
+        let canvas = canvases.get(&output_config.canvas).ok_or_else(|| {
+            anyhow::anyhow!("Output for unknown canvas '{}'", output_config.canvas)
+        })?;
+        let sink = create_sink(&output_config.kind, &canvas)?;
+        tokio::spawn(run_sink(
+            canvas,
+            output.clone(),
+            sink,
+            output_config.fps.unwrap_or(config.output_fps),
+        ));
+    }
+
+    // Devices connected over websocket get frames unless told otherwise.
+    for canvas in canvases.iter().filter(|canvas| {
+        !outputs
+            .iter()
+            .any(|output| output.canvas == *canvas.name && output.kind == OutputKind::Websocket)
+    }) {
+        let sink = create_sink(&OutputKind::Websocket, canvas)?;
+        tokio::spawn(run_sink(
+            canvas.clone(),
+            output.clone(),
+            sink,
+            config.output_fps,
+        ));
+    }
+
+    let sessions = SessionRepo::new();
+    let team_canvas = match &config.team_canvas {
+        Some(name) => canvases
+            .get(name)
+            .ok_or_else(|| anyhow::anyhow!("Team canvas '{name}' does not exist"))?,
+        None => canvases.default_canvas(),
+    };
+    let teams = TeamRepo::new(&config.teams, team_canvas.name.clone());
+    if teams.is_active() {
+        tokio::spawn(run_team_scoring(
+            teams.clone(),
+            team_canvas,
+            sessions.clone(),
+        ));
+    }
+
+    let voting_canvas = config
+        .voting_canvas
+        .as_ref()
+        .map(|name| {
+            canvases
+                .get(name)
+                .ok_or_else(|| anyhow::anyhow!("Voting canvas '{name}' does not exist"))
+        })
+        .transpose()?;
+    if !(1..=MAX_VOTING_ROUND_SECONDS).contains(&config.voting_round_seconds) {
+        anyhow::bail!("VOTING_ROUND_SECONDS must be between 1 and {MAX_VOTING_ROUND_SECONDS}");
+    }
+    let voting = VotingRepo::new(
+        voting_canvas.as_ref().map(|canvas| canvas.name.clone()),
+        Duration::from_secs(config.voting_round_seconds),
+    );
+    if let Some(canvas) = voting_canvas {
+        tokio::spawn(run_voting_rounds(voting.clone(), canvas));
+    }
+
+    let gate = WriteGate::new(teams.clone(), voting.clone());
+
+    if !config.artnet_inputs.is_empty() {
+        let inputs = config
+            .artnet_inputs
+            .iter()
+            .map(|input| {
+                let canvas = canvases.get(&input.canvas).ok_or_else(|| {
+                    anyhow::anyhow!("Art-Net input for unknown canvas '{}'", input.canvas)
+                })?;
+                Ok(ArtnetInput::new(canvas, input)?)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        tokio::spawn(run_artnet_input(
+            config.artnet_bind_address.clone(),
+            inputs,
+            Duration::from_secs(config.artnet_hold_seconds),
+        ));
+    }
+
+    if let Some(bind_address) = &config.ddp_bind_address {
+        let canvas = match &config.ddp_canvas {
+            Some(name) => canvases
+                .get(name)
+                .ok_or_else(|| anyhow::anyhow!("DDP input for unknown canvas '{name}'"))?,
+            None => canvases.default_canvas(),
         };
-        LED_COUNT
-    ]);
 
-    let state = AppState { leds: leds.clone() };
+        tokio::spawn(run_ddp_input(
+            bind_address.clone(),
+            canvas,
+            gate.clone(),
+            Duration::from_secs(config.ddp_hold_seconds),
+        ));
+    }
+
+    if let Some(bind_address) = &config.opc_bind_address {
+        let channels = if config.opc_channels.is_empty() {
+            vec![(1, canvases.default_canvas())]
+        } else {
+            config
+                .opc_channels
+                .iter()
+                .map(|mapping| {
+                    let canvas = canvases.get(&mapping.canvas).ok_or_else(|| {
+                        anyhow::anyhow!("OPC channel for unknown canvas '{}'", mapping.canvas)
+                    })?;
+                    Ok((mapping.channel, canvas))
+                })
+                .collect::<anyhow::Result<_>>()?
+        };
+
+        tokio::spawn(run_opc_server(bind_address.clone(), channels, gate.clone()));
+    }
+
+    if let Some(mqtt) = config.mqtt() {
+        tokio::spawn(run_mqtt(
+            mqtt,
+            canvases.clone(),
+            output.clone(),
+            events.clone(),
+            gate.clone(),
+        ));
+    }
+
+    let webhooks = WebhookRepo::new(config.webhook_allow_private_targets);
+    tokio::spawn(run_webhooks(webhooks.clone(), events.clone()));
+
+    let devices = DeviceRepo::load(config.device_config_path.clone())?;
+
+    let state = AppState {
+        canvases,
+        schedules,
+        output,
+        coordinates,
+        events,
+        webhooks,
+        devices,
+        clients: ClientRepo::new(),
+        sessions,
+        teams,
+        voting,
+    };
 
     let cors = CorsLayer::new()
         .allow_methods(AllowMethods::any())
-        .allow_origin(AllowOrigin::any());
+        .allow_origin(AllowOrigin::any())
+        .allow_headers(AllowHeaders::any())
+        .expose_headers([ETAG, HeaderName::from_static("x-led-generation")]);
 
     let router = Router::new()
-        .nest("/api", api::get_router().layer(cors))
+        .nest("/api", api::get_router().layer(cors.clone()))
+        // WLED apps expect its JSON API at the root.
+        .merge(wled::get_router().layer(cors))
         .with_state(state)
         .route(
             "/light-bulb-generated.svg",
@@ -78,11 +268,7 @@ async fn main() -> anyhow::Result<()> {
 
     let service = router.layer(
         TraceLayer::new_for_http()
-            .make_span_with(
-                DefaultMakeSpan::new()
-                    .include_headers(true)
-                    .level(tracing::Level::INFO),
-            )
+            .make_span_with(make_request_span)
             .on_request(move |request: &Request<axum::body::Body>, _span: &Span| {
                 tracing::info!("Started processing request");
                 ipinfo_lookup(request);
@@ -0,0 +1,28 @@
+use std::sync::LazyLock;
+
+use opentelemetry::{global, metrics::Gauge, KeyValue};
+
+use crate::pipeline::PowerEstimate;
+
+static REQUESTED_CURRENT: LazyLock<Gauge<f64>> = LazyLock::new(|| {
+    global::meter("controlmylights")
+        .f64_gauge("output.requested_current")
+        .with_description("Estimated current the latest frame would draw without limiting")
+        .with_unit("mA")
+        .build()
+});
+
+static OUTPUT_CURRENT: LazyLock<Gauge<f64>> = LazyLock::new(|| {
+    global::meter("controlmylights")
+        .f64_gauge("output.current")
+        .with_description("Estimated current the latest frame draws after power limiting")
+        .with_unit("mA")
+        .build()
+});
+
+/// Records the estimate of the frame last sent for `canvas`.
+pub fn record_power(canvas: &str, power: &PowerEstimate) {
+    let attributes = [KeyValue::new("canvas", canvas.to_string())];
+    REQUESTED_CURRENT.record(power.requested_milliamps.into(), &attributes);
+    OUTPUT_CURRENT.record(power.output_milliamps.into(), &attributes);
+}
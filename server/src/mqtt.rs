@@ -0,0 +1,429 @@
+use std::{collections::HashMap, time::Duration};
+
+use rumqttc::{AsyncClient, Event, LastWill, MqttOptions, Packet, QoS};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::time::{interval, sleep, MissedTickBehavior};
+
+use crate::{
+    effects::{scroll_text, ScrollText},
+    events::EventBus,
+    gate::{WriteError, WriteGate},
+    repo::{
+        canvas::{Canvas, CanvasRepo},
+        output::{CanvasLight, OutputRepo},
+    },
+    routers::scene::apply_scene,
+    types::Color,
+};
+
+/// How often canvases are checked for changes to publish.
+const STATE_INTERVAL: Duration = Duration::from_millis(500);
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+pub struct MqttConfig {
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub topics: MqttTopics,
+}
+
+#[derive(Clone)]
+pub struct MqttTopics {
+    pub client_id: String,
+    pub prefix: String,
+    pub discovery_prefix: String,
+}
+
+impl MqttTopics {
+    fn availability(&self) -> String { format!("{}/status", self.prefix) }
+
+    fn state(&self, canvas: &str) -> String { format!("{}/{canvas}/state", self.prefix) }
+
+    fn command(&self, canvas: &str) -> String { format!("{}/{canvas}/set", self.prefix) }
+
+    fn discovery(&self, canvas: &str) -> String {
+        format!(
+            "{}/light/{}/{canvas}/config",
+            self.discovery_prefix, self.client_id
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "UPPERCASE")]
+enum Power {
+    On,
+    Off,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+struct RgbColor {
+    r: u8,
+    g: u8,
+    b: u8,
+}
+
+/// State in Home Assistant's JSON light schema.
+#[derive(Serialize, PartialEq)]
+struct LightState {
+    state: Power,
+    brightness: u8,
+    color_mode: &'static str,
+    color: RgbColor,
+}
+
+/// A command in Home Assistant's JSON light schema. Its effects are the
+/// canvas' scenes, `scene` is accepted as an alias and `text` starts the
+/// scrolling text effect.
+#[derive(Deserialize)]
+struct LightCommand {
+    state: Option<Power>,
+    brightness: Option<u8>,
+    color: Option<RgbColor>,
+    effect: Option<String>,
+    scene: Option<String>,
+    text: Option<ScrollText>,
+}
+
+impl LightCommand {
+    /// `light` with the command's state and brightness applied.
+    fn light(&self, light: CanvasLight) -> CanvasLight {
+        CanvasLight {
+            on: self.state.map_or(light.on, |state| state == Power::On),
+            brightness: self.brightness.unwrap_or(light.brightness),
+        }
+    }
+}
+
+/// Bridges canvases to an MQTT broker: publishes their state, applies
+/// commands and announces them to Home Assistant as lights.
+pub async fn run_mqtt(
+    config: MqttConfig,
+    canvases: CanvasRepo,
+    output: OutputRepo,
+    events: EventBus,
+    gate: WriteGate,
+) {
+    let topics = &config.topics;
+    let mut options = MqttOptions::new(&topics.client_id, &config.host, config.port);
+    options.set_keep_alive(Duration::from_secs(30));
+    options.set_last_will(LastWill::new(
+        topics.availability(),
+        "offline",
+        QoS::AtLeastOnce,
+        true,
+    ));
+    if let Some(username) = &config.username {
+        options.set_credentials(username, config.password.as_deref().unwrap_or_default());
+    }
+
+    let (client, mut eventloop) = AsyncClient::new(options, 64);
+
+    tokio::spawn(publish_states(
+        topics.clone(),
+        client.clone(),
+        canvases.clone(),
+        output.clone(),
+    ));
+
+    loop {
+        match eventloop.poll().await {
+            Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                tracing::info!("Connected to MQTT broker {}:{}", config.host, config.port);
+
+                // Only polling drains the request channel, which may still be
+                // full of states queued while offline, so this can't wait here.
+                tokio::spawn(announce(topics.clone(), client.clone(), canvases.clone()));
+            }
+            Ok(Event::Incoming(Packet::Publish(publish))) => {
+                let Some(canvas) = canvases
+                    .iter()
+                    .find(|canvas| topics.command(&canvas.name) == publish.topic)
+                else {
+                    continue;
+                };
+
+                match serde_json::from_slice::<LightCommand>(&publish.payload) {
+                    Ok(command) => handle_command(canvas, &output, &events, &gate, command).await,
+                    Err(err) => {
+                        tracing::warn!("Invalid MQTT command on {}: {err}", publish.topic)
+                    }
+                }
+            }
+            Ok(_) => {}
+            Err(err) => {
+                tracing::warn!("MQTT connection failed: {err}");
+                sleep(RECONNECT_DELAY).await;
+            }
+        }
+    }
+}
+
+/// Subscribes to the command topics, which don't survive reconnects with a
+/// clean session, and marks the bridge online.
+async fn announce(topics: MqttTopics, client: AsyncClient, canvases: CanvasRepo) {
+    for canvas in canvases.iter() {
+        if let Err(err) = client
+            .subscribe(topics.command(&canvas.name), QoS::AtLeastOnce)
+            .await
+        {
+            tracing::warn!("Failed to subscribe to MQTT commands: {err}");
+        }
+    }
+
+    let _ = client
+        .publish(topics.availability(), QoS::AtLeastOnce, true, "online")
+        .await;
+}
+
+async fn handle_command(
+    canvas: &Canvas,
+    output: &OutputRepo,
+    events: &EventBus,
+    gate: &WriteGate,
+    command: LightCommand,
+) {
+    if command.state.is_some() || command.brightness.is_some() {
+        match gate.check_bulk::<WriteError>(canvas, []) {
+            Ok(()) => {
+                let light = command.light(output.light(&canvas.name).await);
+                output.set_light(canvas.name.clone(), light).await;
+            }
+            Err(err) => tracing::warn!("Refused MQTT state: {err}"),
+        }
+    }
+
+    if let Some(RgbColor { r, g, b }) = command.color {
+        let mut color = Color {
+            red: r,
+            green: g,
+            blue: b,
+        };
+        match gate.check_bulk::<WriteError>(canvas, [&mut color]) {
+            Ok(()) => canvas.leds.fill(color).await,
+            Err(err) => tracing::warn!("Refused MQTT color: {err}"),
+        }
+    }
+
+    if let Some(scene) = command.scene.or(command.effect) {
+        if let Err(err) = apply_scene(canvas, &scene, events, gate).await {
+            tracing::warn!("Failed to apply scene from MQTT: {err}");
+        }
+    }
+
+    if let Some(mut text) = command.text {
+        match gate.check_bulk::<WriteError>(canvas, [&mut text.color, &mut text.background]) {
+            Ok(()) => {
+                canvas
+                    .effects
+                    .start(
+                        "text",
+                        scroll_text(canvas.leds.clone(), canvas.layout.clone(), text),
+                    )
+                    .await
+            }
+            Err(err) => tracing::warn!("Refused MQTT text: {err}"),
+        }
+    }
+}
+
+/// Publishes the state of every canvas whenever it changes, along with its
+/// discovery payload whenever its scenes change. Both are retained, so the
+/// broker hands them to Home Assistant when it (re)connects.
+async fn publish_states(
+    topics: MqttTopics,
+    client: AsyncClient,
+    canvases: CanvasRepo,
+    output: OutputRepo,
+) {
+    let mut published_states = HashMap::new();
+    let mut published_scenes = HashMap::new();
+    let mut ticker = interval(STATE_INTERVAL);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    loop {
+        ticker.tick().await;
+
+        for canvas in canvases.iter() {
+            let scenes = canvas.scenes.names().await;
+            if published_scenes.get(&canvas.name) != Some(&scenes) {
+                let payload = discovery_payload(&topics, &canvas.name, &scenes);
+                if publish_json(&client, topics.discovery(&canvas.name), &payload).await {
+                    published_scenes.insert(canvas.name.clone(), scenes);
+                }
+            }
+
+            // Canvases aren't single colored, the first led stands in for the
+            // rest.
+            let Some(led) = canvas.leds.get(0).await else {
+                continue;
+            };
+            let light = output.light(&canvas.name).await;
+            let state = LightState {
+                state: if light.on { Power::On } else { Power::Off },
+                brightness: light.brightness,
+                color_mode: "rgb",
+                color: RgbColor {
+                    r: led.color.red,
+                    g: led.color.green,
+                    b: led.color.blue,
+                },
+            };
+
+            if published_states.get(&canvas.name) != Some(&state)
+                && publish_json(&client, topics.state(&canvas.name), &state).await
+            {
+                published_states.insert(canvas.name.clone(), state);
+            }
+        }
+    }
+}
+
+fn discovery_payload(topics: &MqttTopics, canvas: &str, scenes: &[String]) -> serde_json::Value {
+    json!({
+        "name": canvas,
+        "unique_id": format!("{}_{canvas}", topics.client_id),
+        "schema": "json",
+        "state_topic": topics.state(canvas),
+        "command_topic": topics.command(canvas),
+        "availability_topic": topics.availability(),
+        "brightness": true,
+        "supported_color_modes": ["rgb"],
+        "effect": !scenes.is_empty(),
+        "effect_list": scenes,
+        "device": {
+            "identifiers": [topics.client_id],
+            "name": topics.client_id,
+            "manufacturer": env!("CARGO_CRATE_NAME"),
+            "sw_version": env!("CARGO_PKG_VERSION"),
+        },
+    })
+}
+
+/// Queues a retained message, returning whether it was accepted.
+async fn publish_json(client: &AsyncClient, topic: String, payload: &impl Serialize) -> bool {
+    let payload = match serde_json::to_vec(payload) {
+        Ok(payload) => payload,
+        Err(err) => {
+            tracing::error!("Failed to serialize MQTT payload for {topic}: {err}");
+            return false;
+        }
+    };
+
+    match client
+        .publish(&topic, QoS::AtLeastOnce, true, payload)
+        .await
+    {
+        Ok(()) => true,
+        Err(err) => {
+            tracing::warn!("Failed to publish to {topic}: {err}");
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn topics() -> MqttTopics {
+        MqttTopics {
+            client_id: "lights".to_string(),
+            prefix: "lights".to_string(),
+            discovery_prefix: "homeassistant".to_string(),
+        }
+    }
+
+    fn command(payload: serde_json::Value) -> LightCommand {
+        serde_json::from_value(payload).unwrap()
+    }
+
+    #[test]
+    fn commands_parse_home_assistant_json() {
+        let command = command(json!({
+            "state": "ON",
+            "brightness": 128,
+            "color": { "r": 255, "g": 0, "b": 10 },
+            "effect": "sunset",
+        }));
+
+        assert!(command.state == Some(Power::On));
+        assert_eq!(command.brightness, Some(128));
+        assert!(
+            command.color
+                == Some(RgbColor {
+                    r: 255,
+                    g: 0,
+                    b: 10
+                })
+        );
+        assert_eq!(command.effect.as_deref(), Some("sunset"));
+        assert!(command.text.is_none());
+    }
+
+    #[test]
+    fn commands_parse_scrolling_text() {
+        let command = command(json!({ "text": { "message": "hi", "speed": 5.0 } }));
+        let text = command.text.unwrap();
+
+        assert_eq!(text.message, "hi");
+        assert_eq!(text.speed, 5.0);
+        assert_eq!(text.repeat, 1);
+    }
+
+    #[test]
+    fn invalid_commands_are_refused() {
+        assert!(serde_json::from_value::<LightCommand>(json!({ "state": "on" })).is_err());
+        assert!(serde_json::from_value::<LightCommand>(json!({ "brightness": 300 })).is_err());
+    }
+
+    #[test]
+    fn commands_only_change_what_they_set() {
+        let light = CanvasLight {
+            on: true,
+            brightness: 200,
+        };
+
+        assert_eq!(
+            command(json!({ "state": "OFF" })).light(light),
+            CanvasLight {
+                on: false,
+                brightness: 200
+            }
+        );
+        assert_eq!(
+            command(json!({ "brightness": 10 })).light(light),
+            CanvasLight {
+                on: true,
+                brightness: 10
+            }
+        );
+        assert_eq!(command(json!({ "effect": "sunset" })).light(light), light);
+    }
+
+    #[test]
+    fn discovery_announces_a_light_per_canvas() {
+        let payload = discovery_payload(&topics(), "kitchen", &["sunset".to_string()]);
+
+        assert_eq!(payload["unique_id"], "lights_kitchen");
+        assert_eq!(payload["state_topic"], "lights/kitchen/state");
+        assert_eq!(payload["command_topic"], "lights/kitchen/set");
+        assert_eq!(payload["availability_topic"], "lights/status");
+        assert_eq!(payload["effect"], true);
+        assert_eq!(payload["effect_list"], json!(["sunset"]));
+        assert_eq!(
+            topics().discovery("kitchen"),
+            "homeassistant/light/lights/kitchen/config"
+        );
+    }
+
+    #[test]
+    fn discovery_without_scenes_has_no_effects() {
+        let payload = discovery_payload(&topics(), "kitchen", &[]);
+
+        assert_eq!(payload["effect"], false);
+        assert_eq!(payload["effect_list"], json!([]));
+    }
+}
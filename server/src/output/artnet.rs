@@ -0,0 +1,142 @@
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+
+use super::{split_universes, DmxTarget, HostAddress, OutputSink, UNIVERSE_SIZE};
+use crate::pipeline::Frame;
+
+pub const ARTNET_PORT: u16 = 6454;
+pub const ARTNET_ID: &[u8; 8] = b"Art-Net\0";
+pub const OP_DMX: u16 = 0x5000;
+const PROTOCOL_VERSION: u16 = 14;
+/// Port addresses are 15 bits: net, sub-net and universe.
+pub const MAX_PORT_ADDRESS: u16 = 0x7fff;
+/// Art-Net nodes are not expected to cope with more than 44 frames a second.
+const MAX_FPS: u32 = 44;
+
+/// Sends frames to an Art-Net node, or broadcasts them to every node on the
+/// network when no host is configured.
+pub struct ArtnetSink {
+    socket: UdpSocket,
+    target: DmxTarget,
+    /// Unset for broadcasts.
+    host: Option<HostAddress>,
+    sequence: u8,
+}
+
+impl ArtnetSink {
+    pub fn new(target: DmxTarget) -> std::io::Result<Self> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+        socket.set_broadcast(true)?;
+
+        Ok(Self {
+            socket,
+            host: target
+                .host
+                .clone()
+                .map(|host| HostAddress::new(host, ARTNET_PORT)),
+            target,
+            sequence: 0,
+        })
+    }
+}
+
+impl OutputSink for ArtnetSink {
+    fn kind(&self) -> &'static str { "artnet" }
+
+    fn target(&self) -> String { self.target.to_string() }
+
+    fn max_fps(&self) -> u32 { MAX_FPS }
+
+    fn write_frame(&mut self, frame: &Frame) -> std::io::Result<()> {
+        let address = match &mut self.host {
+            Some(host) => host.get()?,
+            None => SocketAddr::from((Ipv4Addr::BROADCAST, ARTNET_PORT)),
+        };
+        // Zero is reserved for nodes that don't look at sequence numbers.
+        self.sequence = self.sequence.wrapping_add(1).max(1);
+
+        for (index, (offset, channels)) in split_universes(&frame.data, self.target.start_channel)
+            .into_iter()
+            .enumerate()
+        {
+            let universe = self.target.universe.wrapping_add(index as u16) & MAX_PORT_ADDRESS;
+            let packet = dmx_packet(universe, self.sequence, offset, channels);
+
+            if let Err(err) = self.socket.send_to(&packet, address) {
+                if let Some(host) = &mut self.host {
+                    host.forget();
+                }
+                return Err(err);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Builds an ArtDmx packet with `channels` placed `offset` slots into the
+/// universe.
+pub fn dmx_packet(universe: u16, sequence: u8, offset: usize, channels: &[u8]) -> Vec<u8> {
+    let slots = (offset + channels.len()).min(UNIVERSE_SIZE);
+    // The data length has to be even and at least 2.
+    let length = (slots + slots % 2).max(2);
+    let [sub_universe, net] = universe.to_le_bytes();
+    let mut packet = Vec::with_capacity(18 + length);
+
+    packet.extend_from_slice(ARTNET_ID);
+    packet.extend_from_slice(&OP_DMX.to_le_bytes());
+    packet.extend_from_slice(&PROTOCOL_VERSION.to_be_bytes());
+    packet.push(sequence);
+    packet.push(0);
+    packet.push(sub_universe);
+    packet.push(net);
+    packet.extend_from_slice(&(length as u16).to_be_bytes());
+    packet.resize(18 + offset.min(UNIVERSE_SIZE), 0);
+    packet.extend_from_slice(&channels[..slots - offset.min(slots)]);
+    packet.resize(18 + length, 0);
+
+    packet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dmx_packet_has_artdmx_header() {
+        let packet = dmx_packet(0x0123, 5, 0, &[1, 2, 3, 4]);
+
+        assert_eq!(&packet[..8], ARTNET_ID);
+        assert_eq!(packet[8..10], OP_DMX.to_le_bytes());
+        assert_eq!(packet[10..12], PROTOCOL_VERSION.to_be_bytes());
+        assert_eq!(packet[12], 5);
+        // Sub-net and universe first, then the net.
+        assert_eq!(packet[14], 0x23);
+        assert_eq!(packet[15], 0x01);
+        assert_eq!(packet[16..18], 4u16.to_be_bytes());
+        assert_eq!(&packet[18..], &[1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn dmx_packet_pads_to_even_length() {
+        let packet = dmx_packet(0, 1, 2, &[7]);
+
+        assert_eq!(packet[16..18], 4u16.to_be_bytes());
+        assert_eq!(&packet[18..], &[0, 0, 7, 0]);
+    }
+
+    #[test]
+    fn dmx_packet_has_at_least_two_slots() {
+        let packet = dmx_packet(0, 1, 0, &[]);
+
+        assert_eq!(packet[16..18], 2u16.to_be_bytes());
+        assert_eq!(&packet[18..], &[0, 0]);
+    }
+
+    #[test]
+    fn dmx_packet_stops_at_universe_end() {
+        let packet = dmx_packet(0, 1, UNIVERSE_SIZE - 1, &[1, 2, 3]);
+
+        assert_eq!(packet.len(), 18 + UNIVERSE_SIZE);
+        assert_eq!(packet[packet.len() - 1], 1);
+    }
+}
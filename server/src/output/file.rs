@@ -0,0 +1,87 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{Seek, SeekFrom, Write},
+    path::PathBuf,
+};
+
+use super::OutputSink;
+use crate::pipeline::Frame;
+
+/// Writes every frame as raw channel data to a file, mostly useful for
+/// debugging or to feed another program through a named pipe. Frames all have
+/// the same size, so they can be told apart without any framing.
+///
+/// Named pipes and devices get a stream of frames, while regular files only
+/// ever hold the latest one instead of growing forever.
+pub struct FileSink {
+    path: PathBuf,
+    file: File,
+    overwrite: bool,
+}
+
+impl FileSink {
+    pub fn new(path: PathBuf) -> std::io::Result<Self> {
+        // Regular files are truncated to the size of every frame written.
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(false)
+            .open(&path)?;
+        let overwrite = file.metadata()?.is_file();
+
+        Ok(Self {
+            path,
+            file,
+            overwrite,
+        })
+    }
+}
+
+impl OutputSink for FileSink {
+    fn kind(&self) -> &'static str { "file" }
+
+    fn target(&self) -> String { self.path.display().to_string() }
+
+    fn write_frame(&mut self, frame: &Frame) -> std::io::Result<()> {
+        if self.overwrite {
+            self.file.seek(SeekFrom::Start(0))?;
+            self.file.write_all(&frame.data)?;
+            return self.file.set_len(frame.data.len() as u64);
+        }
+
+        self.file.write_all(&frame.data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::pipeline::PowerEstimate;
+
+    fn frame(data: &[u8]) -> Frame {
+        Frame {
+            data: data.to_vec(),
+            power: PowerEstimate {
+                requested_milliamps: 0.0,
+                output_milliamps: 0.0,
+                budget_milliamps: None,
+                scale: 1.0,
+            },
+        }
+    }
+
+    #[test]
+    fn regular_files_only_hold_the_latest_frame() {
+        let path = std::env::temp_dir().join(format!("frames-{}", uuid::Uuid::new_v4()));
+        fs::write(&path, b"stale data from before").unwrap();
+
+        let mut sink = FileSink::new(path.clone()).unwrap();
+        sink.write_frame(&frame(&[1, 2, 3, 4, 5, 6])).unwrap();
+        sink.write_frame(&frame(&[7, 8, 9])).unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), vec![7, 8, 9]);
+        fs::remove_file(path).unwrap();
+    }
+}
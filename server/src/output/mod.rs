@@ -0,0 +1,316 @@
+//! Sinks pushing canvas frames to devices, each driven at its own frame rate.
+
+pub mod artnet;
+pub mod file;
+pub mod sacn;
+pub mod serial;
+pub mod websocket;
+
+use std::{
+    net::{SocketAddr, ToSocketAddrs},
+    path::PathBuf,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use tokio::{
+    task::block_in_place,
+    time::{interval, MissedTickBehavior},
+};
+
+use crate::{
+    metrics::record_power,
+    pipeline::Frame,
+    repo::{canvas::Canvas, output::OutputRepo},
+};
+
+/// Channels in a DMX universe.
+pub const UNIVERSE_SIZE: usize = 512;
+const MAX_FPS: u32 = 60;
+const DEFAULT_BAUD_RATE: u32 = 115_200;
+/// Failed lookups are retried after this long, doubling up to
+/// [`MAX_LOOKUP_BACKOFF`] while they keep failing.
+const INITIAL_LOOKUP_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_LOOKUP_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Something frames can be written to, like a network protocol or a file.
+/// Writes happen on a runtime thread allowed to block, so implementations can
+/// use plain blocking IO.
+pub trait OutputSink: Send {
+    /// Short name of the kind of sink, e.g. `sacn`.
+    fn kind(&self) -> &'static str;
+
+    /// Where frames go, for status reports.
+    fn target(&self) -> String;
+
+    /// Highest frame rate the receiving end is expected to cope with.
+    fn max_fps(&self) -> u32 { MAX_FPS }
+
+    fn write_frame(&mut self, frame: &Frame) -> std::io::Result<()>;
+}
+
+/// What an output sends frames to, declared as:
+/// - `websocket`: devices connected to `/leds/ws?colors_only=true`
+/// - `sacn:host/universe[/start_channel]`: E1.31, `host` may be `multicast`
+/// - `artnet:host/universe[/start_channel]`: ArtDmx, `host` may be `broadcast`
+/// - `file:path`: raw frames streamed to a named pipe, or the latest frame
+///   kept in a regular file
+/// - `serial:path[@baud_rate]`: Adalight over a serial port, 115200 baud by
+///   default
+#[derive(Debug, Clone, PartialEq)]
+pub enum OutputKind {
+    Websocket,
+    Sacn(DmxTarget),
+    Artnet(DmxTarget),
+    File(PathBuf),
+    Serial { path: String, baud_rate: u32 },
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error(
+    "'{0}' is not an output like canvas=websocket, canvas=sacn:host/universe[/start_channel], \
+     canvas=artnet:host/universe[/start_channel], canvas=file:<path> or \
+     canvas=serial:<path>[@baud_rate], optionally followed by ?fps=<fps>"
+)]
+pub struct ParseOutputKindError(pub(crate) String);
+
+impl FromStr for OutputKind {
+    type Err = ParseOutputKindError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || ParseOutputKindError(s.to_string());
+
+        match s.split_once(':') {
+            None if s == "websocket" => Ok(OutputKind::Websocket),
+            Some(("sacn", target)) => Ok(OutputKind::Sacn(target.parse().map_err(|_| error())?)),
+            Some(("artnet", target)) => {
+                Ok(OutputKind::Artnet(target.parse().map_err(|_| error())?))
+            }
+            Some(("file", path)) if !path.is_empty() => Ok(OutputKind::File(PathBuf::from(path))),
+            Some(("serial", spec)) => {
+                let (path, baud_rate) = match spec.split_once('@') {
+                    Some((path, baud_rate)) => (path, baud_rate.parse().map_err(|_| error())?),
+                    None => (spec, DEFAULT_BAUD_RATE),
+                };
+
+                if path.is_empty() || baud_rate == 0 {
+                    return Err(error());
+                }
+
+                Ok(OutputKind::Serial {
+                    path: path.to_string(),
+                    baud_rate,
+                })
+            }
+            _ => Err(error()),
+        }
+    }
+}
+
+/// Where a DMX over IP output sends to, `host/universe[/start_channel]`.
+/// Without a host (`multicast`/`broadcast`) every receiver is reached.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DmxTarget {
+    pub host: Option<String>,
+    pub universe: u16,
+    pub start_channel: u16,
+}
+
+impl FromStr for DmxTarget {
+    type Err = ParseOutputKindError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || ParseOutputKindError(s.to_string());
+        let mut parts = s.split('/');
+
+        let host = parts
+            .next()
+            .filter(|host| !host.is_empty())
+            .ok_or_else(error)?;
+        let universe = parts
+            .next()
+            .and_then(|universe| universe.parse().ok())
+            .ok_or_else(error)?;
+        let start_channel = match parts.next() {
+            Some(channel) => channel.parse().map_err(|_| error())?,
+            None => 1,
+        };
+
+        if parts.next().is_some() || !(1..=512).contains(&start_channel) {
+            return Err(error());
+        }
+
+        Ok(Self {
+            host: (!matches!(host, "multicast" | "broadcast")).then(|| host.to_string()),
+            universe,
+            start_channel,
+        })
+    }
+}
+
+impl std::fmt::Display for DmxTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let host = self.host.as_deref().unwrap_or("*");
+        write!(f, "{host}/{}/{}", self.universe, self.start_channel)
+    }
+}
+
+/// A unicast receiver's address. Looking it up blocks, so it's only done when
+/// the sink is created and again after a send to it fails, backing off while
+/// the lookups fail.
+pub struct HostAddress {
+    host: String,
+    port: u16,
+    address: Option<SocketAddr>,
+    backoff: Duration,
+    retry_at: Option<Instant>,
+}
+
+impl HostAddress {
+    pub fn new(host: String, port: u16) -> Self {
+        let mut address = Self {
+            host,
+            port,
+            address: None,
+            backoff: INITIAL_LOOKUP_BACKOFF,
+            retry_at: None,
+        };
+        let _ = address.get();
+
+        address
+    }
+
+    pub fn get(&mut self) -> std::io::Result<SocketAddr> {
+        if let Some(address) = self.address {
+            return Ok(address);
+        }
+        if self
+            .retry_at
+            .is_some_and(|retry_at| Instant::now() < retry_at)
+        {
+            return Err(std::io::Error::other(format!(
+                "No address for {} yet, retrying lookup later",
+                self.host
+            )));
+        }
+
+        match lookup(&self.host, self.port) {
+            Ok(address) => {
+                self.address = Some(address);
+                self.backoff = INITIAL_LOOKUP_BACKOFF;
+                self.retry_at = None;
+                Ok(address)
+            }
+            Err(err) => {
+                self.retry_at = Some(Instant::now() + self.backoff);
+                self.backoff = (self.backoff * 2).min(MAX_LOOKUP_BACKOFF);
+                Err(err)
+            }
+        }
+    }
+
+    /// Looks the address up again on the next send, e.g. after the host
+    /// moved.
+    pub fn forget(&mut self) { self.address = None }
+}
+
+fn lookup(host: &str, port: u16) -> std::io::Result<SocketAddr> {
+    (host, port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| std::io::Error::other(format!("No address for {host}")))
+}
+
+pub fn create_sink(kind: &OutputKind, canvas: &Canvas) -> std::io::Result<Box<dyn OutputSink>> {
+    Ok(match kind {
+        OutputKind::Websocket => Box::new(websocket::WebsocketSink::new(canvas.frames.clone())),
+        OutputKind::Sacn(target) => Box::new(sacn::SacnSink::new(target.clone())?),
+        OutputKind::Artnet(target) => Box::new(artnet::ArtnetSink::new(target.clone())?),
+        OutputKind::File(path) => Box::new(file::FileSink::new(path.clone())?),
+        OutputKind::Serial { path, baud_rate } => Box::new(serial::SerialSink::new(
+            path.clone(),
+            *baud_rate,
+            canvas.leds.led_count(),
+        )),
+    })
+}
+
+/// Writes the canvas to `sink` at `fps`, reporting every write to `output`.
+pub async fn run_sink(canvas: Canvas, output: OutputRepo, mut sink: Box<dyn OutputSink>, fps: u32) {
+    let fps = fps.clamp(1, sink.max_fps());
+    let id = output
+        .register_sink(canvas.name.clone(), sink.kind(), sink.target(), fps)
+        .await;
+    let mut last_error = None;
+    let mut ticker = interval(Duration::from_secs_f64(1.0 / f64::from(fps)));
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    loop {
+        ticker.tick().await;
+
+        let frame = render(&canvas, &output).await;
+        record_power(&canvas.name, &frame.power);
+        let result = block_in_place(|| sink.write_frame(&frame)).map_err(|err| err.to_string());
+
+        // Only log changes, a sink that can't reach its device fails every frame.
+        let error = result.as_ref().err();
+        if error != last_error.as_ref() {
+            match error {
+                Some(err) => {
+                    tracing::warn!("Output {} to {} failed: {err}", sink.kind(), sink.target())
+                }
+                None => tracing::info!("Output {} to {} recovered", sink.kind(), sink.target()),
+            }
+            last_error = error.cloned();
+        }
+
+        output.record_sink_write(id, result).await;
+    }
+}
+
+/// Runs the canvas' current colors through the output pipeline.
+pub async fn render(canvas: &Canvas, output: &OutputRepo) -> Frame {
+    let snapshot = canvas.leds.snapshot().await;
+    output
+        .canvas_pipeline(&canvas.name)
+        .await
+        .frame(snapshot.leds.into_iter().map(|led| led.color))
+}
+
+/// Splits RGB data across consecutive universes without splitting a pixel
+/// between two of them, which is what most pixel controllers expect. The first
+/// universe starts at the 1-based `start_channel`, the following ones at 1.
+pub fn split_universes(data: &[u8], start_channel: u16) -> Vec<(usize, &[u8])> {
+    let first_offset = usize::from(start_channel.clamp(1, UNIVERSE_SIZE as u16)) - 1;
+    let first_len = ((UNIVERSE_SIZE - first_offset) / 3 * 3).min(data.len());
+    let (first, rest) = data.split_at(first_len);
+
+    std::iter::once((first_offset, first))
+        .chain(rest.chunks(UNIVERSE_SIZE / 3 * 3).map(|chunk| (0, chunk)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_lookups_back_off() {
+        let mut address = HostAddress::new("lights.invalid".to_string(), 5568);
+        let retry_at = address.retry_at.expect("the lookup failed");
+        assert_eq!(address.backoff, INITIAL_LOOKUP_BACKOFF * 2);
+
+        // Within the backoff nothing is looked up.
+        assert!(address.get().is_err());
+        assert_eq!(address.retry_at, Some(retry_at));
+        assert_eq!(address.backoff, INITIAL_LOOKUP_BACKOFF * 2);
+    }
+
+    #[test]
+    fn successful_lookups_are_kept() {
+        let mut address = HostAddress::new("127.0.0.1".to_string(), 5568);
+
+        assert_eq!(address.get().unwrap(), "127.0.0.1:5568".parse().unwrap());
+        assert!(address.retry_at.is_none());
+    }
+}
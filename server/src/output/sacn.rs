@@ -0,0 +1,165 @@
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+
+use uuid::Uuid;
+
+use super::{split_universes, DmxTarget, HostAddress, OutputSink, UNIVERSE_SIZE};
+use crate::pipeline::Frame;
+
+pub const SACN_PORT: u16 = 5568;
+const ACN_PACKET_IDENTIFIER: &[u8; 12] = b"ASC-E1.17\0\0\0";
+const SOURCE_NAME: &str = "controlmylights";
+const DEFAULT_PRIORITY: u8 = 100;
+
+/// Sends frames to an E1.31 (sACN) receiver, multicast or unicast.
+pub struct SacnSink {
+    socket: UdpSocket,
+    target: DmxTarget,
+    /// Unset for multicast, which goes to a group per universe.
+    host: Option<HostAddress>,
+    cid: Uuid,
+    sequences: Vec<u8>,
+}
+
+impl SacnSink {
+    pub fn new(target: DmxTarget) -> std::io::Result<Self> {
+        Ok(Self {
+            socket: UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?,
+            host: target
+                .host
+                .clone()
+                .map(|host| HostAddress::new(host, SACN_PORT)),
+            target,
+            cid: Uuid::new_v4(),
+            sequences: Vec::new(),
+        })
+    }
+}
+
+impl OutputSink for SacnSink {
+    fn kind(&self) -> &'static str { "sacn" }
+
+    fn target(&self) -> String { self.target.to_string() }
+
+    fn write_frame(&mut self, frame: &Frame) -> std::io::Result<()> {
+        let universes = split_universes(&frame.data, self.target.start_channel);
+        self.sequences.resize(universes.len(), 0);
+
+        for (index, (offset, channels)) in universes.into_iter().enumerate() {
+            let universe = self.target.universe.wrapping_add(index as u16);
+            let address = match &mut self.host {
+                Some(host) => host.get()?,
+                None => multicast_address(universe),
+            };
+            let packet = data_packet(self.cid, universe, self.sequences[index], offset, channels);
+            self.sequences[index] = self.sequences[index].wrapping_add(1);
+
+            if let Err(err) = self.socket.send_to(&packet, address) {
+                if let Some(host) = &mut self.host {
+                    host.forget();
+                }
+                return Err(err);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The standard multicast group of the universe, used when no host is
+/// configured.
+fn multicast_address(universe: u16) -> SocketAddr {
+    let [high, low] = universe.to_be_bytes();
+    SocketAddr::from((Ipv4Addr::new(239, 255, high, low), SACN_PORT))
+}
+
+fn flags_and_length(length: usize) -> [u8; 2] { (0x7000 | length as u16).to_be_bytes() }
+
+/// Builds an E1.31 data packet with `channels` placed `offset` slots into the
+/// universe.
+pub fn data_packet(
+    cid: Uuid,
+    universe: u16,
+    sequence: u8,
+    offset: usize,
+    channels: &[u8],
+) -> Vec<u8> {
+    let slots = (offset + channels.len()).min(UNIVERSE_SIZE);
+    let length = 126 + slots;
+    let mut packet = Vec::with_capacity(length);
+
+    // Root layer
+    packet.extend_from_slice(&0x0010u16.to_be_bytes());
+    packet.extend_from_slice(&0x0000u16.to_be_bytes());
+    packet.extend_from_slice(ACN_PACKET_IDENTIFIER);
+    packet.extend_from_slice(&flags_and_length(length - 16));
+    packet.extend_from_slice(&0x0000_0004u32.to_be_bytes());
+    packet.extend_from_slice(cid.as_bytes());
+
+    // Framing layer
+    packet.extend_from_slice(&flags_and_length(length - 38));
+    packet.extend_from_slice(&0x0000_0002u32.to_be_bytes());
+    let mut source_name = [0u8; 64];
+    source_name[..SOURCE_NAME.len()].copy_from_slice(SOURCE_NAME.as_bytes());
+    packet.extend_from_slice(&source_name);
+    packet.push(DEFAULT_PRIORITY);
+    packet.extend_from_slice(&0u16.to_be_bytes());
+    packet.push(sequence);
+    packet.push(0);
+    packet.extend_from_slice(&universe.to_be_bytes());
+
+    // DMP layer
+    packet.extend_from_slice(&flags_and_length(length - 115));
+    packet.push(0x02);
+    packet.push(0xa1);
+    packet.extend_from_slice(&0x0000u16.to_be_bytes());
+    packet.extend_from_slice(&0x0001u16.to_be_bytes());
+    packet.extend_from_slice(&(slots as u16 + 1).to_be_bytes());
+    packet.push(0x00);
+    packet.resize(126 + offset.min(UNIVERSE_SIZE), 0);
+    packet.extend_from_slice(&channels[..slots - offset.min(slots)]);
+
+    packet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn data_packet_has_e131_layers() {
+        let cid = Uuid::new_v4();
+        let packet = data_packet(cid, 7, 42, 0, &[1, 2, 3]);
+
+        assert_eq!(packet.len(), 126 + 3);
+        assert_eq!(&packet[4..16], ACN_PACKET_IDENTIFIER);
+        assert_eq!(packet[16..18], (0x7000u16 | (129 - 16)).to_be_bytes());
+        assert_eq!(&packet[22..38], cid.as_bytes());
+        assert_eq!(packet[38..40], (0x7000u16 | (129 - 38)).to_be_bytes());
+        assert_eq!(&packet[44..44 + SOURCE_NAME.len()], SOURCE_NAME.as_bytes());
+        assert_eq!(packet[108], DEFAULT_PRIORITY);
+        assert_eq!(packet[111], 42);
+        assert_eq!(packet[113..115], 7u16.to_be_bytes());
+        assert_eq!(packet[115..117], (0x7000u16 | (129 - 115)).to_be_bytes());
+        // Property count covers the start code and the slots.
+        assert_eq!(packet[123..125], 4u16.to_be_bytes());
+        assert_eq!(packet[125], 0);
+        assert_eq!(&packet[126..], &[1, 2, 3]);
+    }
+
+    #[test]
+    fn data_packet_places_channels_at_offset() {
+        let packet = data_packet(Uuid::nil(), 1, 0, 3, &[9, 8]);
+
+        assert_eq!(packet[123..125], 6u16.to_be_bytes());
+        assert_eq!(&packet[126..], &[0, 0, 0, 9, 8]);
+    }
+
+    #[test]
+    fn data_packet_stops_at_universe_end() {
+        let packet = data_packet(Uuid::nil(), 1, 0, UNIVERSE_SIZE - 1, &[1, 2, 3]);
+
+        assert_eq!(packet.len(), 126 + UNIVERSE_SIZE);
+        assert_eq!(packet[123..125], (UNIVERSE_SIZE as u16 + 1).to_be_bytes());
+        assert_eq!(packet[packet.len() - 1], 1);
+    }
+}
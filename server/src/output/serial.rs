@@ -0,0 +1,140 @@
+use std::{io::Write, time::Duration};
+
+use serialport::SerialPort;
+
+use super::{OutputSink, MAX_FPS};
+use crate::pipeline::Frame;
+
+const ADALIGHT_MAGIC: &[u8; 3] = b"Ada";
+const ADALIGHT_HEADER_LENGTH: u32 = 6;
+/// A start bit, 8 data bits and a stop bit go over the wire per byte.
+const BITS_PER_BYTE: u32 = 10;
+const WRITE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Writes frames to a serial device using the Adalight protocol. The port is
+/// (re)opened lazily, so a device can be plugged in after startup or be
+/// unplugged and come back.
+pub struct SerialSink {
+    path: String,
+    baud_rate: u32,
+    led_count: usize,
+    port: Option<Box<dyn SerialPort>>,
+}
+
+impl SerialSink {
+    pub fn new(path: String, baud_rate: u32, led_count: usize) -> Self {
+        Self {
+            path,
+            baud_rate,
+            led_count,
+            port: None,
+        }
+    }
+}
+
+impl OutputSink for SerialSink {
+    fn kind(&self) -> &'static str { "serial" }
+
+    fn target(&self) -> String { format!("{}@{}", self.path, self.baud_rate) }
+
+    /// Frames beyond what the link carries would only back up in the port.
+    fn max_fps(&self) -> u32 {
+        let packet_length = ADALIGHT_HEADER_LENGTH
+            .saturating_add(u32::try_from(self.led_count * 3).unwrap_or(u32::MAX));
+
+        (self.baud_rate / BITS_PER_BYTE / packet_length).clamp(1, MAX_FPS)
+    }
+
+    fn write_frame(&mut self, frame: &Frame) -> std::io::Result<()> {
+        let port = match &mut self.port {
+            Some(port) => port,
+            None => self.port.insert(
+                serialport::new(&self.path, self.baud_rate)
+                    .timeout(WRITE_TIMEOUT)
+                    .open()?,
+            ),
+        };
+
+        let result = port.write_all(&adalight_packet(&frame.data));
+        if result.is_err() {
+            self.port = None;
+        }
+
+        result
+    }
+}
+
+/// `Ada`, the LED count minus one as a big endian u16, a checksum of the
+/// count and then the RGB data.
+pub fn adalight_packet(data: &[u8]) -> Vec<u8> {
+    let [high, low] = ((data.len() / 3).max(1) as u16 - 1).to_be_bytes();
+    let mut packet = Vec::with_capacity(6 + data.len());
+
+    packet.extend_from_slice(ADALIGHT_MAGIC);
+    packet.extend_from_slice(&[high, low, high ^ low ^ 0x55]);
+    packet.extend_from_slice(data);
+
+    packet
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use serialport::TTYPort;
+
+    use super::*;
+    use crate::pipeline::PowerEstimate;
+
+    #[test]
+    fn adalight_packet_has_header_and_checksum() {
+        let packet = adalight_packet(&[1, 2, 3, 4, 5, 6]);
+
+        // One less than the two leds.
+        assert_eq!(&packet[..6], b"Ada\x00\x01\x54");
+        assert_eq!(&packet[6..], &[1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn adalight_packet_counts_leds_in_both_bytes() {
+        let packet = adalight_packet(&[0; 300 * 3]);
+        let [high, low] = 299u16.to_be_bytes();
+
+        assert_eq!(packet[3..6], [high, low, high ^ low ^ 0x55]);
+        assert_eq!(packet.len(), 6 + 900);
+    }
+
+    #[test]
+    fn max_fps_fits_the_link() {
+        assert_eq!(SerialSink::new(String::new(), 115_200, 150).max_fps(), 25);
+        assert_eq!(
+            SerialSink::new(String::new(), 1_000_000, 10).max_fps(),
+            MAX_FPS
+        );
+        assert_eq!(SerialSink::new(String::new(), 9600, 1000).max_fps(), 1);
+    }
+
+    #[test]
+    fn writes_frames_to_a_serial_port() {
+        let (mut master, slave) = TTYPort::pair().expect("pseudo-terminal");
+        let path = slave.name().expect("pseudo-terminal path");
+        let mut sink = SerialSink::new(path, 115_200, 2);
+        let frame = Frame {
+            data: vec![10, 20, 30, 40, 50, 60],
+            power: PowerEstimate {
+                requested_milliamps: 0.0,
+                output_milliamps: 0.0,
+                budget_milliamps: None,
+                scale: 1.0,
+            },
+        };
+
+        sink.write_frame(&frame).unwrap();
+        sink.write_frame(&frame).unwrap();
+
+        let mut received = [0u8; 24];
+        master.read_exact(&mut received).unwrap();
+        assert_eq!(received[..12], adalight_packet(&frame.data)[..]);
+        assert_eq!(received[12..], received[..12]);
+    }
+}
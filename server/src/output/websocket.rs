@@ -0,0 +1,45 @@
+use axum::body::Bytes;
+use tokio::sync::watch;
+
+use super::OutputSink;
+use crate::pipeline::Frame;
+
+/// Latest frame of a canvas, for the devices connected over websocket.
+pub type DeviceFrames = watch::Sender<DeviceFrame>;
+
+#[derive(Clone, Default)]
+pub struct DeviceFrame {
+    /// Incremented for every new frame, so readers can tell how many they
+    /// missed.
+    pub sequence: u64,
+    pub data: Bytes,
+}
+
+/// Hands frames to the devices connected to `/leds/ws?colors_only=true`.
+/// They are only woken up when the frame actually changed.
+pub struct WebsocketSink {
+    frames: DeviceFrames,
+}
+
+impl WebsocketSink {
+    pub fn new(frames: DeviceFrames) -> Self { Self { frames } }
+}
+
+impl OutputSink for WebsocketSink {
+    fn kind(&self) -> &'static str { "websocket" }
+
+    fn target(&self) -> String { "/leds/ws?colors_only=true".to_string() }
+
+    fn write_frame(&mut self, frame: &Frame) -> std::io::Result<()> {
+        self.frames.send_if_modified(|current| {
+            let changed = current.data != frame.data;
+            if changed {
+                current.sequence += 1;
+                current.data = Bytes::copy_from_slice(&frame.data);
+            }
+            changed
+        });
+
+        Ok(())
+    }
+}
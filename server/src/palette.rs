@@ -0,0 +1,149 @@
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::types::{Color, HexColor};
+
+/// What happens to colors missing from a canvas' palette.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PaletteMode {
+    /// Paint the closest palette color instead.
+    Snap,
+    /// Refuse the write.
+    Reject,
+}
+
+/// The only colors users may paint a canvas with.
+#[derive(Serialize, Clone, Debug)]
+pub struct Palette {
+    pub mode: PaletteMode,
+    pub colors: Vec<HexColor>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum PaletteError {
+    #[error(
+        "'{0}' is not a palette like snap:#rrggbb[:#rrggbb...] or reject:#rrggbb[:#rrggbb...]"
+    )]
+    Parse(String),
+    #[error("{} is not in the canvas' palette", String::from(HexColor(*.0)))]
+    OffPalette(Color),
+}
+
+impl FromStr for Palette {
+    type Err = PaletteError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || PaletteError::Parse(s.to_string());
+        let (mode, colors) = s.split_once(':').ok_or_else(error)?;
+
+        let mode = match mode {
+            "snap" => PaletteMode::Snap,
+            "reject" => PaletteMode::Reject,
+            _ => return Err(error()),
+        };
+        let colors = colors
+            .split(':')
+            .map(|color| color.parse().map(HexColor))
+            .collect::<Result<_, _>>()
+            .map_err(|_| error())?;
+
+        Ok(Self { mode, colors })
+    }
+}
+
+impl Palette {
+    /// The color to paint instead of `color`.
+    pub fn apply(&self, color: Color) -> Result<Color, PaletteError> {
+        if self.contains(color) {
+            return Ok(color);
+        }
+
+        match self.mode {
+            PaletteMode::Snap => Ok(self.snap(color)),
+            PaletteMode::Reject => Err(PaletteError::OffPalette(color)),
+        }
+    }
+
+    pub fn contains(&self, color: Color) -> bool { self.colors.contains(&HexColor(color)) }
+
+    /// The closest palette color, whatever the mode.
+    pub fn snap(&self, color: Color) -> Color {
+        self.colors
+            .iter()
+            .map(|HexColor(candidate)| *candidate)
+            .min_by_key(|candidate| distance(color, *candidate))
+            .unwrap_or(color)
+    }
+}
+
+/// Squared "redmean" distance, a cheap approximation of how different two
+/// colors look.
+fn distance(a: Color, b: Color) -> u32 {
+    let mean_red = (a.red as u32 + b.red as u32) / 2;
+    let red = (a.red as i32 - b.red as i32).unsigned_abs();
+    let green = (a.green as i32 - b.green as i32).unsigned_abs();
+    let blue = (a.blue as i32 - b.blue as i32).unsigned_abs();
+
+    (((512 + mean_red) * red * red) >> 8)
+        + 4 * green * green
+        + (((767 - mean_red) * blue * blue) >> 8)
+}
+
+/// The color to paint instead of `color` on a canvas with `palette`, if any.
+pub fn apply_palette(palette: Option<&Palette>, color: Color) -> Result<Color, PaletteError> {
+    palette.map_or(Ok(color), |palette| palette.apply(color))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: Color = Color {
+        red: 255,
+        green: 0,
+        blue: 0,
+    };
+    const BLUE: Color = Color {
+        red: 0,
+        green: 0,
+        blue: 255,
+    };
+    const DARK_RED: Color = Color {
+        red: 150,
+        green: 20,
+        blue: 10,
+    };
+
+    #[test]
+    fn parses_mode_and_colors() {
+        let palette: Palette = "reject:#ff0000:#0000ff".parse().unwrap();
+
+        assert_eq!(palette.mode, PaletteMode::Reject);
+        assert_eq!(palette.colors, [HexColor(RED), HexColor(BLUE)]);
+        assert!("fade:#ff0000".parse::<Palette>().is_err());
+        assert!("snap:red".parse::<Palette>().is_err());
+    }
+
+    #[test]
+    fn snaps_to_the_nearest_color() {
+        let palette: Palette = "snap:#ff0000:#0000ff".parse().unwrap();
+
+        assert_eq!(palette.apply(BLUE).unwrap(), BLUE);
+        assert_eq!(palette.apply(DARK_RED).unwrap(), RED);
+    }
+
+    #[test]
+    fn rejects_colors_missing_from_the_palette() {
+        let palette: Palette = "reject:#ff0000:#0000ff".parse().unwrap();
+
+        assert_eq!(palette.apply(RED).unwrap(), RED);
+        assert!(matches!(
+            palette.apply(DARK_RED),
+            Err(PaletteError::OffPalette(color)) if color == DARK_RED
+        ));
+        // Snapping ignores the mode.
+        assert_eq!(palette.snap(DARK_RED), RED);
+    }
+}
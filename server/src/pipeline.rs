@@ -0,0 +1,305 @@
+use serde::{Deserialize, Serialize};
+use serde_inline_default::serde_inline_default;
+
+use crate::types::{Color, HexColor};
+
+/// Order in which the physical strip expects the color channels of each LED.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum ChannelOrder {
+    #[default]
+    Rgb,
+    Rbg,
+    Grb,
+    Gbr,
+    Brg,
+    Bgr,
+}
+
+impl ChannelOrder {
+    pub fn arrange(self, [red, green, blue]: [u8; 3]) -> [u8; 3] {
+        match self {
+            ChannelOrder::Rgb => [red, green, blue],
+            ChannelOrder::Rbg => [red, blue, green],
+            ChannelOrder::Grb => [green, red, blue],
+            ChannelOrder::Gbr => [green, blue, red],
+            ChannelOrder::Brg => [blue, red, green],
+            ChannelOrder::Bgr => [blue, green, red],
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct Gamma {
+    pub red: f32,
+    pub green: f32,
+    pub blue: f32,
+}
+
+/// Parameters for estimating how much current a frame draws.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct PowerSettings {
+    /// Current drawn by a single channel of a single LED at full intensity.
+    pub milliamps_per_channel: f32,
+    /// Current drawn by each LED's driver even when it is off.
+    pub idle_milliamps_per_led: f32,
+    /// When set, frames that would draw more than this are dimmed to fit.
+    pub budget_milliamps: Option<f32>,
+}
+
+/// How logical colors are turned into what gets sent to physical devices.
+#[serde_inline_default]
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct OutputSettings {
+    /// Blanks every output while keeping the canvases untouched.
+    #[serde_inline_default(true)]
+    pub on: bool,
+    pub brightness: u8,
+    pub gamma: Gamma,
+    pub white_balance: HexColor,
+    pub channel_order: ChannelOrder,
+    pub power: PowerSettings,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct PowerEstimate {
+    /// What the frame would draw without the power limiter.
+    pub requested_milliamps: f32,
+    /// What the frame draws after the power limiter.
+    pub output_milliamps: f32,
+    pub budget_milliamps: Option<f32>,
+    /// Factor the channels were scaled by to stay within budget.
+    pub scale: f32,
+}
+
+#[derive(Clone, Debug)]
+pub struct Frame {
+    pub data: Vec<u8>,
+    pub power: PowerEstimate,
+}
+
+/// [`OutputSettings`] compiled into per-channel lookup tables.
+#[derive(Clone, Debug)]
+pub struct OutputPipeline {
+    settings: OutputSettings,
+    lookup: [[u8; 256]; 3],
+}
+
+impl OutputPipeline {
+    pub fn new(settings: OutputSettings) -> Self {
+        let HexColor(white_balance) = settings.white_balance;
+        let channels = [
+            (settings.gamma.red, white_balance.red),
+            (settings.gamma.green, white_balance.green),
+            (settings.gamma.blue, white_balance.blue),
+        ];
+
+        let lookup = channels.map(|(gamma, white_point)| {
+            let brightness = if settings.on { settings.brightness } else { 0 };
+            let scale = f32::from(white_point) / 255.0 * f32::from(brightness) / 255.0;
+
+            std::array::from_fn(|value| {
+                let normalized = value as f32 / 255.0;
+                (normalized.powf(gamma.max(0.01)) * scale * 255.0).round() as u8
+            })
+        });
+
+        Self { settings, lookup }
+    }
+
+    pub fn settings(&self) -> OutputSettings { self.settings }
+
+    pub fn apply(&self, color: Color) -> [u8; 3] {
+        self.settings.channel_order.arrange([
+            self.lookup[0][color.red as usize],
+            self.lookup[1][color.green as usize],
+            self.lookup[2][color.blue as usize],
+        ])
+    }
+
+    pub fn frame(&self, colors: impl IntoIterator<Item = Color>) -> Frame {
+        let PowerSettings {
+            milliamps_per_channel,
+            idle_milliamps_per_led,
+            budget_milliamps,
+        } = self.settings.power;
+
+        let mut data: Vec<u8> = colors
+            .into_iter()
+            .flat_map(|color| self.apply(color))
+            .collect();
+
+        let idle_milliamps = (data.len() / 3) as f32 * idle_milliamps_per_led;
+        let channel_milliamps = data
+            .iter()
+            .map(|&channel| f32::from(channel) / 255.0 * milliamps_per_channel)
+            .sum::<f32>();
+        let requested_milliamps = idle_milliamps + channel_milliamps;
+
+        // The idle draw can't be dimmed away, so only the channels are scaled.
+        let scale = match budget_milliamps {
+            Some(budget) if requested_milliamps > budget && channel_milliamps > 0.0 => {
+                ((budget - idle_milliamps) / channel_milliamps).clamp(0.0, 1.0)
+            }
+            _ => 1.0,
+        };
+
+        if scale < 1.0 {
+            for channel in data.iter_mut() {
+                *channel = (f32::from(*channel) * scale) as u8;
+            }
+        }
+
+        Frame {
+            data,
+            power: PowerEstimate {
+                requested_milliamps,
+                output_milliamps: idle_milliamps + channel_milliamps * scale,
+                budget_milliamps,
+                scale,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> OutputSettings {
+        OutputSettings {
+            on: true,
+            brightness: 255,
+            gamma: Gamma {
+                red: 1.0,
+                green: 1.0,
+                blue: 1.0,
+            },
+            white_balance: HexColor(Color {
+                red: 255,
+                green: 255,
+                blue: 255,
+            }),
+            channel_order: ChannelOrder::Rgb,
+            power: PowerSettings {
+                milliamps_per_channel: 20.0,
+                idle_milliamps_per_led: 1.0,
+                budget_milliamps: None,
+            },
+        }
+    }
+
+    const ORANGE: Color = Color {
+        red: 255,
+        green: 128,
+        blue: 10,
+    };
+
+    #[test]
+    fn linear_gamma_at_full_brightness_is_the_identity() {
+        let pipeline = OutputPipeline::new(settings());
+
+        for value in 0..=255 {
+            let color = Color {
+                red: value,
+                green: value,
+                blue: value,
+            };
+            assert_eq!(pipeline.apply(color), [value; 3]);
+        }
+    }
+
+    #[test]
+    fn gamma_brightness_and_white_balance_scale_channels() {
+        let pipeline = OutputPipeline::new(OutputSettings {
+            brightness: 128,
+            gamma: Gamma {
+                red: 2.0,
+                green: 1.0,
+                blue: 1.0,
+            },
+            white_balance: HexColor(Color {
+                red: 255,
+                green: 255,
+                blue: 0,
+            }),
+            ..settings()
+        });
+
+        // Brightness halves every channel and the white point drops blue.
+        assert_eq!(pipeline.apply(ORANGE), [128, 64, 0]);
+        // Half red squared is about a quarter, halved again by brightness.
+        let [red, ..] = pipeline.apply(Color {
+            red: 128,
+            green: 0,
+            blue: 0,
+        });
+        assert_eq!(red, 32);
+    }
+
+    #[test]
+    fn channels_are_reordered_last() {
+        let pipeline = OutputPipeline::new(OutputSettings {
+            channel_order: ChannelOrder::Grb,
+            ..settings()
+        });
+
+        assert_eq!(pipeline.apply(ORANGE), [128, 255, 10]);
+        assert_eq!(ChannelOrder::Bgr.arrange([1, 2, 3]), [3, 2, 1]);
+        assert_eq!(ChannelOrder::Brg.arrange([1, 2, 3]), [3, 1, 2]);
+    }
+
+    #[test]
+    fn switching_off_blanks_every_channel() {
+        let pipeline = OutputPipeline::new(OutputSettings {
+            on: false,
+            ..settings()
+        });
+
+        assert_eq!(pipeline.apply(ORANGE), [0, 0, 0]);
+    }
+
+    #[test]
+    fn frames_within_budget_are_untouched() {
+        let pipeline = OutputPipeline::new(OutputSettings {
+            power: PowerSettings {
+                budget_milliamps: Some(1000.0),
+                ..settings().power
+            },
+            ..settings()
+        });
+
+        let frame = pipeline.frame([ORANGE; 2]);
+
+        assert_eq!(frame.data, [255, 128, 10, 255, 128, 10]);
+        assert_eq!(frame.power.scale, 1.0);
+        assert_eq!(
+            frame.power.requested_milliamps,
+            frame.power.output_milliamps
+        );
+    }
+
+    #[test]
+    fn limiter_scales_channels_to_the_budget() {
+        let white = Color {
+            red: 255,
+            green: 255,
+            blue: 255,
+        };
+        let pipeline = OutputPipeline::new(OutputSettings {
+            power: PowerSettings {
+                // 10 idle plus 600 for the channels of 10 white leds.
+                budget_milliamps: Some(310.0),
+                ..settings().power
+            },
+            ..settings()
+        });
+
+        let frame = pipeline.frame([white; 10]);
+
+        assert_eq!(frame.power.requested_milliamps, 610.0);
+        assert_eq!(frame.power.scale, 0.5);
+        assert_eq!(frame.power.output_milliamps, 310.0);
+        assert!(frame.data.iter().all(|&channel| channel == 127));
+    }
+}
@@ -0,0 +1,112 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use crate::{
+    config::{CanvasConfig, LayoutConfig, PaletteConfig},
+    effects::EffectRunner,
+    layout::{Layout, LayoutError},
+    output::websocket::DeviceFrames,
+    palette::Palette,
+    repo::{led::LedRepo, presence::PresenceRepo, scene::SceneRepo},
+};
+
+/// A named strip of LEDs with its own state.
+#[derive(Clone)]
+pub struct Canvas {
+    pub name: Arc<str>,
+    pub leds: LedRepo,
+    pub layout: Arc<Layout>,
+    pub scenes: SceneRepo,
+    pub effects: EffectRunner,
+    pub frames: DeviceFrames,
+    pub presence: PresenceRepo,
+    /// Restricts the colors users may paint with when set.
+    pub palette: Option<Arc<Palette>>,
+}
+
+#[derive(Clone)]
+pub struct CanvasRepo(Arc<CanvasRepoInner>);
+
+pub struct CanvasRepoInner {
+    canvases: BTreeMap<Arc<str>, Canvas>,
+    default: Canvas,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum CanvasRepoError {
+    #[error("Canvas '{0}' is declared more than once")]
+    Duplicate(String),
+    #[error("Default canvas '{0}' is not declared")]
+    MissingDefault(String),
+    #[error("Layout declared for unknown canvas '{0}'")]
+    UnknownLayoutCanvas(String),
+    #[error("Palette declared for unknown canvas '{0}'")]
+    UnknownPaletteCanvas(String),
+    #[error("Invalid layout for canvas '{0}': {1}")]
+    Layout(String, LayoutError),
+}
+
+impl CanvasRepo {
+    pub fn new(
+        configs: &[CanvasConfig],
+        layouts: &[LayoutConfig],
+        palettes: &[PaletteConfig],
+        default: &str,
+    ) -> Result<Self, CanvasRepoError> {
+        if let Some(layout) = layouts
+            .iter()
+            .find(|layout| !configs.iter().any(|config| config.name == layout.canvas))
+        {
+            return Err(CanvasRepoError::UnknownLayoutCanvas(layout.canvas.clone()));
+        }
+        if let Some(palette) = palettes
+            .iter()
+            .find(|palette| !configs.iter().any(|config| config.name == palette.canvas))
+        {
+            return Err(CanvasRepoError::UnknownPaletteCanvas(
+                palette.canvas.clone(),
+            ));
+        }
+
+        let mut canvases = BTreeMap::new();
+
+        for config in configs {
+            let name: Arc<str> = config.name.as_str().into();
+            let layout = match layouts.iter().find(|layout| layout.canvas == config.name) {
+                Some(layout) => Layout::new(layout.kind.clone(), config.led_count)
+                    .map_err(|err| CanvasRepoError::Layout(config.name.clone(), err))?,
+                None => Layout::linear(config.led_count),
+            };
+            let canvas = Canvas {
+                name: name.clone(),
+                leds: LedRepo::new(vec![config.default_color; config.led_count]),
+                layout: Arc::new(layout),
+                scenes: SceneRepo::new(),
+                effects: EffectRunner::new(),
+                frames: DeviceFrames::new(Default::default()),
+                presence: PresenceRepo::new(),
+                palette: palettes
+                    .iter()
+                    .find(|palette| palette.canvas == config.name)
+                    .map(|palette| Arc::new(palette.palette.clone())),
+            };
+
+            if canvases.insert(name, canvas).is_some() {
+                return Err(CanvasRepoError::Duplicate(config.name.clone()));
+            }
+        }
+
+        let default = canvases
+            .get(default)
+            .cloned()
+            .ok_or_else(|| CanvasRepoError::MissingDefault(default.to_string()))?;
+
+        Ok(Self(Arc::new(CanvasRepoInner { canvases, default })))
+    }
+
+    pub fn get(&self, name: &str) -> Option<Canvas> { self.0.canvases.get(name).cloned() }
+
+    /// The canvas served by the routes that don't name one, e.g. `/api/leds`.
+    pub fn default_canvas(&self) -> Canvas { self.0.default.clone() }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Canvas> { self.0.canvases.values() }
+}
@@ -0,0 +1,150 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::time::Instant;
+use uuid::Uuid;
+
+/// A client whose frames take longer than this to go out is behind.
+const BEHIND_LAG: Duration = Duration::from_secs(1);
+
+/// A live `/leds/ws` connection.
+#[derive(Serialize, Clone, Debug)]
+pub struct Client {
+    pub id: Uuid,
+    pub canvas: Arc<str>,
+    /// Set for devices, see [`super::device::DeviceRepo`].
+    pub device_id: Option<String>,
+    pub colors_only: bool,
+    pub connected_at: DateTime<Utc>,
+    pub frames_sent: u64,
+    /// Frames replaced by a newer one before they could be sent.
+    pub frames_dropped: u64,
+    /// Time the latest frame spent queued and being sent, or the frame being
+    /// sent has spent so far if that's longer.
+    pub send_lag_ms: Option<f64>,
+    /// Whether the client currently can't keep up with its frames.
+    pub behind: bool,
+    #[serde(skip)]
+    sending_since: Option<Instant>,
+}
+
+/// Websocket clients currently connected, for spotting the slow ones.
+#[derive(Clone, Default)]
+pub struct ClientRepo(Arc<Mutex<BTreeMap<Uuid, Client>>>);
+
+impl ClientRepo {
+    pub fn new() -> Self { Self::default() }
+
+    pub fn list(&self) -> Vec<Client> {
+        self.0
+            .lock()
+            .expect("clients lock poisoned")
+            .values()
+            .cloned()
+            .map(|mut client| {
+                // A stuck send never completes to record its lag.
+                if let Some(lag) = client.sending_since.map(|since| since.elapsed()) {
+                    let lag_ms = lag.as_secs_f64() * 1000.0;
+                    if client.send_lag_ms.is_none_or(|latest| latest < lag_ms) {
+                        client.send_lag_ms = Some(lag_ms);
+                        client.behind |= lag > BEHIND_LAG;
+                    }
+                }
+                client
+            })
+            .collect()
+    }
+
+    /// How many browsers and devices are connected to `canvas`.
+    pub fn counts(&self, canvas: &str) -> (usize, usize) {
+        let clients = self.0.lock().expect("clients lock poisoned");
+        let (devices, browsers): (Vec<_>, Vec<_>) = clients
+            .values()
+            .filter(|client| &*client.canvas == canvas)
+            .partition(|client| client.device_id.is_some());
+
+        (browsers.len(), devices.len())
+    }
+
+    /// Registers a client, which stays listed as long as the returned handle.
+    pub fn connect(
+        &self,
+        id: Uuid,
+        canvas: Arc<str>,
+        device_id: Option<String>,
+        colors_only: bool,
+    ) -> ClientHandle {
+        self.0.lock().expect("clients lock poisoned").insert(
+            id,
+            Client {
+                id,
+                canvas,
+                device_id,
+                colors_only,
+                connected_at: Utc::now(),
+                frames_sent: 0,
+                frames_dropped: 0,
+                send_lag_ms: None,
+                behind: false,
+                sending_since: None,
+            },
+        );
+
+        ClientHandle {
+            repo: self.clone(),
+            id,
+        }
+    }
+
+    fn update(&self, id: Uuid, update: impl FnOnce(&mut Client)) {
+        if let Some(client) = self.0.lock().expect("clients lock poisoned").get_mut(&id) {
+            update(client);
+        }
+    }
+}
+
+pub struct ClientHandle {
+    repo: ClientRepo,
+    id: Uuid,
+}
+
+impl ClientHandle {
+    /// Marks a frame queued at `queued_at` as being sent.
+    pub fn record_sending(&self, queued_at: Instant) {
+        self.repo
+            .update(self.id, |client| client.sending_since = Some(queued_at))
+    }
+
+    /// Records a sent frame, returning whether the client is behind.
+    pub fn record_frame(&self, lag: Duration) -> bool {
+        let behind = lag > BEHIND_LAG;
+        self.repo.update(self.id, |client| {
+            client.frames_sent += 1;
+            client.send_lag_ms = Some(lag.as_secs_f64() * 1000.0);
+            client.behind = behind;
+            client.sending_since = None;
+        });
+
+        behind
+    }
+
+    pub fn record_dropped(&self) {
+        self.repo
+            .update(self.id, |client| client.frames_dropped += 1)
+    }
+}
+
+impl Drop for ClientHandle {
+    fn drop(&mut self) {
+        self.repo
+            .0
+            .lock()
+            .expect("clients lock poisoned")
+            .remove(&self.id);
+    }
+}
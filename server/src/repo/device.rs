@@ -0,0 +1,294 @@
+use std::{
+    collections::BTreeMap,
+    io,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{watch, Mutex as AsyncMutex};
+
+use crate::pipeline::ChannelOrder;
+
+/// Offline devices beyond this many are forgotten, least recently seen first.
+const MAX_OFFLINE_DEVICES: usize = 256;
+
+/// What a device tells about itself when it connects.
+#[derive(Serialize, Clone, Debug)]
+pub struct DeviceInfo {
+    pub id: String,
+    pub firmware: Option<String>,
+    pub led_count: Option<usize>,
+    pub channel_order: Option<ChannelOrder>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Device {
+    #[serde(flatten)]
+    pub info: DeviceInfo,
+    pub canvas: Arc<str>,
+    pub ip: String,
+    pub online: bool,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    pub connected_at: Option<DateTime<Utc>>,
+    pub reconnects: u64,
+    pub frames_sent: u64,
+    /// Frames replaced by a newer one before they could be sent.
+    pub frames_dropped: u64,
+    /// Time to hand the latest frame to the connection.
+    pub frame_send_ms: Option<f64>,
+    /// Round trip of the latest ping the device answered.
+    pub latency_ms: Option<f64>,
+    #[serde(skip)]
+    connections: usize,
+}
+
+/// Settings pushed to a device, overriding what it was flashed with. Unset
+/// fields leave the device's own value alone.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DeviceConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_brightness: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub led_count: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel_order: Option<ChannelOrder>,
+    /// Frames are also sent no faster than this.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fps: Option<u32>,
+    /// How often the device gets pinged, it can assume the connection is dead
+    /// after missing a few.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub heartbeat_seconds: Option<u64>,
+}
+
+/// Hardware clients that identified themselves on `/leds/ws`, kept after they
+/// disconnect so an offline strip still shows up.
+#[derive(Clone)]
+pub struct DeviceRepo(Arc<DeviceRepoInner>);
+
+pub struct DeviceRepoInner {
+    devices: Mutex<BTreeMap<String, Device>>,
+    /// Kept apart from `devices` so devices can be configured before they
+    /// first connect.
+    configs: Mutex<BTreeMap<String, watch::Sender<DeviceConfig>>>,
+    config_path: PathBuf,
+    /// Serializes writes of `config_path`.
+    persist: AsyncMutex<()>,
+}
+
+impl DeviceRepo {
+    /// Loads the device configs saved at `config_path`, if any.
+    pub fn load(config_path: PathBuf) -> io::Result<Self> {
+        let configs: BTreeMap<String, DeviceConfig> = match std::fs::read(&config_path) {
+            Ok(json) => serde_json::from_slice(&json)?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(err) => return Err(err),
+        };
+
+        Ok(Self(Arc::new(DeviceRepoInner {
+            devices: Mutex::new(BTreeMap::new()),
+            configs: Mutex::new(
+                configs
+                    .into_iter()
+                    .map(|(id, config)| (id, watch::Sender::new(config)))
+                    .collect(),
+            ),
+            config_path,
+            persist: AsyncMutex::new(()),
+        })))
+    }
+
+    pub fn list(&self) -> Vec<Device> {
+        self.0
+            .devices
+            .lock()
+            .expect("devices lock poisoned")
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    /// Registers a connection of the device, which lasts as long as the
+    /// returned handle.
+    pub fn connect(&self, info: DeviceInfo, canvas: Arc<str>, ip: String) -> DeviceHandle {
+        let now = Utc::now();
+        let mut devices = self.0.devices.lock().expect("devices lock poisoned");
+        let id = info.id.clone();
+
+        match devices.get_mut(&id) {
+            Some(device) => {
+                device.info = info;
+                device.canvas = canvas;
+                device.ip = ip;
+                device.online = true;
+                device.last_seen = now;
+                device.connected_at = Some(now);
+                device.reconnects += 1;
+                device.connections += 1;
+            }
+            None => {
+                forget_offline(&mut devices, MAX_OFFLINE_DEVICES - 1, &self.0.configs);
+                devices.insert(
+                    id.clone(),
+                    Device {
+                        info,
+                        canvas,
+                        ip,
+                        online: true,
+                        first_seen: now,
+                        last_seen: now,
+                        connected_at: Some(now),
+                        reconnects: 0,
+                        frames_sent: 0,
+                        frames_dropped: 0,
+                        frame_send_ms: None,
+                        latency_ms: None,
+                        connections: 1,
+                    },
+                );
+            }
+        }
+
+        DeviceHandle {
+            repo: self.clone(),
+            id,
+        }
+    }
+
+    pub fn config(&self, id: &str) -> DeviceConfig {
+        self.0
+            .configs
+            .lock()
+            .expect("device configs lock poisoned")
+            .get(id)
+            .map(|config| *config.borrow())
+            .unwrap_or_default()
+    }
+
+    /// Pushes `config` to the connected device and saves it for its next
+    /// connections.
+    pub async fn set_config(&self, id: &str, config: DeviceConfig) -> io::Result<()> {
+        self.config_sender(id).send_replace(config);
+
+        let _persist = self.0.persist.lock().await;
+        let configs: BTreeMap<String, DeviceConfig> = self
+            .0
+            .configs
+            .lock()
+            .expect("device configs lock poisoned")
+            .iter()
+            .filter(|(_, config)| *config.borrow() != DeviceConfig::default())
+            .map(|(id, config)| (id.clone(), *config.borrow()))
+            .collect();
+        let json = serde_json::to_vec_pretty(&configs)?;
+
+        // Written aside first so a crash can't leave a truncated file behind.
+        let temp_path = self.0.config_path.with_extension("json.tmp");
+        tokio::fs::write(&temp_path, json).await?;
+        tokio::fs::rename(&temp_path, &self.0.config_path).await
+    }
+
+    fn config_sender(&self, id: &str) -> watch::Sender<DeviceConfig> {
+        self.0
+            .configs
+            .lock()
+            .expect("device configs lock poisoned")
+            .entry(id.to_string())
+            .or_insert_with(|| watch::Sender::new(DeviceConfig::default()))
+            .clone()
+    }
+
+    fn update(&self, id: &str, update: impl FnOnce(&mut Device)) {
+        if let Some(device) = self
+            .0
+            .devices
+            .lock()
+            .expect("devices lock poisoned")
+            .get_mut(id)
+        {
+            update(device);
+        }
+    }
+}
+
+/// Forgets the least recently seen offline devices until at most `keep` are
+/// left, along with their configs unless they were edited.
+fn forget_offline(
+    devices: &mut BTreeMap<String, Device>,
+    keep: usize,
+    configs: &Mutex<BTreeMap<String, watch::Sender<DeviceConfig>>>,
+) {
+    let mut offline: Vec<(DateTime<Utc>, String)> = devices
+        .values()
+        .filter(|device| !device.online)
+        .map(|device| (device.last_seen, device.info.id.clone()))
+        .collect();
+    if offline.len() <= keep {
+        return;
+    }
+
+    offline.sort_unstable();
+    let mut configs = configs.lock().expect("device configs lock poisoned");
+    for (_, id) in offline.drain(..offline.len() - keep) {
+        devices.remove(&id);
+        if configs
+            .get(&id)
+            .is_some_and(|config| *config.borrow() == DeviceConfig::default())
+        {
+            configs.remove(&id);
+        }
+    }
+}
+
+/// A live connection of a device, marking it offline once dropped.
+pub struct DeviceHandle {
+    repo: DeviceRepo,
+    id: String,
+}
+
+impl DeviceHandle {
+    /// The device's config, marked as changed whenever it gets edited.
+    pub fn config(&self) -> watch::Receiver<DeviceConfig> {
+        self.repo.config_sender(&self.id).subscribe()
+    }
+
+    pub fn seen(&self) {
+        self.repo
+            .update(&self.id, |device| device.last_seen = Utc::now())
+    }
+
+    pub fn record_frame(&self, duration: Duration) {
+        self.repo.update(&self.id, |device| {
+            device.frames_sent += 1;
+            device.frame_send_ms = Some(duration.as_secs_f64() * 1000.0);
+        })
+    }
+
+    pub fn record_dropped(&self) {
+        self.repo
+            .update(&self.id, |device| device.frames_dropped += 1)
+    }
+
+    pub fn record_latency(&self, round_trip: Duration) {
+        self.repo.update(&self.id, |device| {
+            device.last_seen = Utc::now();
+            device.latency_ms = Some(round_trip.as_secs_f64() * 1000.0);
+        })
+    }
+}
+
+impl Drop for DeviceHandle {
+    fn drop(&mut self) {
+        self.repo.update(&self.id, |device| {
+            device.connections = device.connections.saturating_sub(1);
+            if device.connections == 0 {
+                device.online = false;
+                device.connected_at = None;
+            }
+        })
+    }
+}
@@ -1,12 +1,17 @@
-use std::sync::{
-    atomic::{AtomicUsize, Ordering},
-    Arc,
+use std::{
+    ops::Range,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
 };
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use tokio::sync::RwLock;
+use tokio::sync::{watch, RwLock};
 use tracing::{instrument, Level};
+use uuid::Uuid;
 
 use crate::types::Color;
 
@@ -14,6 +19,9 @@ use crate::types::Color;
 pub struct Led {
     pub color: Color,
     pub last_updated: DateTime<Utc>,
+    /// Session that last set this led on its own, cleared by bulk writes.
+    #[serde(default)]
+    pub painted_by: Option<Uuid>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -27,52 +35,201 @@ pub struct LedRepo(Arc<LedRepoInner>);
 
 pub struct LedRepoInner {
     generation: AtomicUsize,
+    generation_changed: watch::Sender<usize>,
+    led_count: usize,
     leds: RwLock<Vec<Led>>,
+    holds: Mutex<Vec<Hold>>,
+}
+
+/// A range of leds reserved for a higher priority source (e.g. a lighting
+/// desk), during which regular writes to it are refused.
+struct Hold {
+    range: Range<usize>,
+    until: Instant,
 }
 
 #[derive(thiserror::Error, Debug)]
 pub enum LedRepoError {
     #[error("Id {0} is out of bounds")]
     OutOfBounds(usize),
+    #[error("Led {0} is currently controlled by another source")]
+    Held(usize),
 }
 
 impl LedRepo {
     pub fn new(initial_colors: impl IntoIterator<Item = Color>) -> Self {
         let now = Utc::now();
+        let leds: Vec<Led> = initial_colors
+            .into_iter()
+            .map(|color| Led {
+                color,
+                last_updated: now,
+                painted_by: None,
+            })
+            .collect();
 
         Self(Arc::new(LedRepoInner {
             generation: 0.into(),
-            leds: RwLock::new(
-                initial_colors
-                    .into_iter()
-                    .map(|color| Led {
-                        color,
-                        last_updated: now,
-                    })
-                    .collect(),
-            ),
+            generation_changed: watch::Sender::new(0),
+            led_count: leds.len(),
+            leds: RwLock::new(leds),
+            holds: Mutex::new(Vec::new()),
         }))
     }
 
     pub async fn get(&self, id: usize) -> Option<Led> { self.0.leds.read().await.get(id).cloned() }
 
     #[instrument(skip(self), level=Level::TRACE)]
-    pub async fn set(&self, id: usize, color: Color) -> Result<Led, LedRepoError> {
+    pub async fn set(
+        &self,
+        id: usize,
+        color: Color,
+        painted_by: Option<Uuid>,
+    ) -> Result<Led, LedRepoError> {
         let mut lock = self.0.leds.write().await;
         let current_led = lock.get_mut(id).ok_or(LedRepoError::OutOfBounds(id))?;
 
+        if self.is_held(id) {
+            return Err(LedRepoError::Held(id));
+        }
+
         current_led.color = color;
         current_led.last_updated = Utc::now();
+        current_led.painted_by = painted_by;
 
-        let previous_generation = self.0.generation.fetch_add(1, Ordering::AcqRel);
+        let previous_generation = self.bump_generation();
 
         tracing::trace!("Led updated (previous generation was {previous_generation})!");
 
         Ok(*current_led)
     }
 
+    /// Sets several leds at once, either all of them or none if any id is out
+    /// of bounds. Held leds are skipped.
+    #[instrument(skip_all, level=Level::TRACE)]
+    pub async fn set_many(
+        &self,
+        colors: impl IntoIterator<Item = (usize, Color)>,
+    ) -> Result<(), LedRepoError> {
+        self.write_many(colors, true).await
+    }
+
+    /// Like [`LedRepo::set_many`], but also writes held leds. Meant for the
+    /// source holding them.
+    #[instrument(skip_all, level=Level::TRACE)]
+    pub async fn override_many(
+        &self,
+        colors: impl IntoIterator<Item = (usize, Color)>,
+    ) -> Result<(), LedRepoError> {
+        self.write_many(colors, false).await
+    }
+
+    async fn write_many(
+        &self,
+        colors: impl IntoIterator<Item = (usize, Color)>,
+        respect_holds: bool,
+    ) -> Result<(), LedRepoError> {
+        let colors: Vec<(usize, Color)> = colors.into_iter().collect();
+        let mut lock = self.0.leds.write().await;
+
+        if let Some((id, _)) = colors.iter().find(|(id, _)| *id >= lock.len()) {
+            return Err(LedRepoError::OutOfBounds(*id));
+        }
+
+        let now = Utc::now();
+        for (id, color) in colors {
+            if respect_holds && self.is_held(id) {
+                continue;
+            }
+
+            lock[id].color = color;
+            lock[id].last_updated = now;
+            lock[id].painted_by = None;
+        }
+
+        let previous_generation = self.bump_generation();
+
+        tracing::trace!("Leds updated (previous generation was {previous_generation})!");
+
+        Ok(())
+    }
+
+    #[instrument(skip(self), level=Level::TRACE)]
+    pub async fn fill(&self, color: Color) {
+        let mut lock = self.0.leds.write().await;
+        let now = Utc::now();
+
+        for (id, led) in lock.iter_mut().enumerate() {
+            if self.is_held(id) {
+                continue;
+            }
+
+            led.color = color;
+            led.last_updated = now;
+            led.painted_by = None;
+        }
+
+        let previous_generation = self.bump_generation();
+
+        tracing::trace!("Leds filled (previous generation was {previous_generation})!");
+    }
+
+    pub fn led_count(&self) -> usize { self.0.led_count }
+
+    /// Reserves `range` for `duration`, refreshing any hold on the same range.
+    pub fn hold(&self, range: Range<usize>, duration: Duration) {
+        let mut holds = self.0.holds.lock().expect("holds lock poisoned");
+        let now = Instant::now();
+
+        holds.retain(|hold| hold.until > now && hold.range != range);
+        holds.push(Hold {
+            range,
+            until: now + duration,
+        });
+    }
+
+    pub fn is_held(&self, id: usize) -> bool {
+        let now = Instant::now();
+
+        self.0
+            .holds
+            .lock()
+            .expect("holds lock poisoned")
+            .iter()
+            .any(|hold| hold.until > now && hold.range.contains(&id))
+    }
+
+    /// Whether any led is currently held by another source.
+    pub fn has_holds(&self) -> bool {
+        let now = Instant::now();
+
+        self.0
+            .holds
+            .lock()
+            .expect("holds lock poisoned")
+            .iter()
+            .any(|hold| hold.until > now)
+    }
+
     pub fn generation(&self) -> usize { self.0.generation.load(Ordering::Acquire) }
 
+    /// Returns the previous generation.
+    fn bump_generation(&self) -> usize {
+        let previous_generation = self.0.generation.fetch_add(1, Ordering::AcqRel);
+        self.0
+            .generation_changed
+            .send_replace(previous_generation + 1);
+
+        previous_generation
+    }
+
+    /// Resolves once the generation reached at least `generation`.
+    pub async fn wait_for_generation(&self, generation: usize) {
+        let mut receiver = self.0.generation_changed.subscribe();
+        // The sender lives as long as the repo, so this can't fail.
+        let _ = receiver.wait_for(|current| *current >= generation).await;
+    }
+
     pub async fn snapshot(&self) -> LedRepoSnapshot {
         LedRepoSnapshot {
             generation: self.generation(),
@@ -1 +1,12 @@
+pub mod canvas;
+pub mod client;
+pub mod device;
 pub mod led;
+pub mod output;
+pub mod presence;
+pub mod scene;
+pub mod schedule;
+pub mod session;
+pub mod team;
+pub mod voting;
+pub mod webhook;
@@ -0,0 +1,234 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::sync::RwLock;
+
+use crate::pipeline::{OutputPipeline, OutputSettings};
+
+/// Holds the output pipeline shared by everything that sends frames to
+/// physical devices.
+#[derive(Clone)]
+pub struct OutputRepo(Arc<OutputRepoInner>);
+
+pub struct OutputRepoInner {
+    generation: AtomicUsize,
+    pipeline: RwLock<OutputPipeline>,
+    lights: RwLock<HashMap<Arc<str>, CanvasLight>>,
+    sinks: RwLock<Vec<SinkStatus>>,
+}
+
+/// Switches and dims a single canvas on top of the [`OutputSettings`], for
+/// light-like integrations (WLED apps, Home Assistant) that control a canvas
+/// rather than every output.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct CanvasLight {
+    pub on: bool,
+    pub brightness: u8,
+}
+
+impl Default for CanvasLight {
+    fn default() -> Self {
+        Self {
+            on: true,
+            brightness: 255,
+        }
+    }
+}
+
+/// Health of a running [`crate::output::OutputSink`].
+#[derive(Serialize, Clone, Debug)]
+pub struct SinkStatus {
+    pub id: usize,
+    pub canvas: Arc<str>,
+    pub kind: &'static str,
+    pub target: String,
+    pub fps: u32,
+    pub frames_written: u64,
+    pub errors: u64,
+    pub last_error: Option<String>,
+    pub last_write: Option<DateTime<Utc>>,
+    /// Whether the latest write succeeded.
+    pub healthy: bool,
+}
+
+impl OutputRepo {
+    pub fn new(settings: OutputSettings) -> Self {
+        Self(Arc::new(OutputRepoInner {
+            generation: 0.into(),
+            pipeline: RwLock::new(OutputPipeline::new(settings)),
+            lights: RwLock::new(HashMap::new()),
+            sinks: RwLock::new(Vec::new()),
+        }))
+    }
+
+    pub async fn pipeline(&self) -> OutputPipeline { self.0.pipeline.read().await.clone() }
+
+    pub async fn settings(&self) -> OutputSettings { self.0.pipeline.read().await.settings() }
+
+    pub async fn set_settings(&self, settings: OutputSettings) {
+        *self.0.pipeline.write().await = OutputPipeline::new(settings);
+        self.0.generation.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// The pipeline with the light of `canvas` applied.
+    pub async fn canvas_pipeline(&self, canvas: &str) -> OutputPipeline {
+        let light = self.light(canvas).await;
+        let pipeline = self.pipeline().await;
+        if light == CanvasLight::default() {
+            return pipeline;
+        }
+
+        let mut settings = pipeline.settings();
+        settings.on &= light.on;
+        settings.brightness =
+            (u16::from(settings.brightness) * u16::from(light.brightness) / 255) as u8;
+
+        OutputPipeline::new(settings)
+    }
+
+    pub async fn light(&self, canvas: &str) -> CanvasLight {
+        self.0
+            .lights
+            .read()
+            .await
+            .get(canvas)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub async fn set_light(&self, canvas: Arc<str>, light: CanvasLight) {
+        self.0.lights.write().await.insert(canvas, light);
+        self.0.generation.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// Bumped every time the settings or a light change, so device connections know to
+    /// resend their frame even if no LED changed.
+    pub fn generation(&self) -> usize { self.0.generation.load(Ordering::Acquire) }
+
+    /// Returns the id to report writes of the sink with.
+    pub async fn register_sink(
+        &self,
+        canvas: Arc<str>,
+        kind: &'static str,
+        target: String,
+        fps: u32,
+    ) -> usize {
+        let mut sinks = self.0.sinks.write().await;
+        let id = sinks.len();
+        sinks.push(SinkStatus {
+            id,
+            canvas,
+            kind,
+            target,
+            fps,
+            frames_written: 0,
+            errors: 0,
+            last_error: None,
+            last_write: None,
+            healthy: true,
+        });
+
+        id
+    }
+
+    pub async fn record_sink_write(&self, id: usize, result: Result<(), String>) {
+        let mut sinks = self.0.sinks.write().await;
+        let Some(status) = sinks.get_mut(id) else {
+            return;
+        };
+
+        status.last_write = Some(Utc::now());
+        status.healthy = result.is_ok();
+        match result {
+            Ok(()) => status.frames_written += 1,
+            Err(err) => {
+                status.errors += 1;
+                status.last_error = Some(err);
+            }
+        }
+    }
+
+    pub async fn sinks(&self) -> Vec<SinkStatus> { self.0.sinks.read().await.clone() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        pipeline::{ChannelOrder, Gamma, PowerSettings},
+        types::{Color, HexColor},
+    };
+
+    const WHITE: Color = Color {
+        red: 255,
+        green: 255,
+        blue: 255,
+    };
+
+    fn output() -> OutputRepo {
+        OutputRepo::new(OutputSettings {
+            on: true,
+            brightness: 128,
+            gamma: Gamma {
+                red: 1.0,
+                green: 1.0,
+                blue: 1.0,
+            },
+            white_balance: HexColor(WHITE),
+            channel_order: ChannelOrder::Rgb,
+            power: PowerSettings {
+                milliamps_per_channel: 20.0,
+                idle_milliamps_per_led: 1.0,
+                budget_milliamps: None,
+            },
+        })
+    }
+
+    #[tokio::test]
+    async fn lights_only_affect_their_canvas() {
+        let output = output();
+        let generation = output.generation();
+
+        output
+            .set_light(
+                "kitchen".into(),
+                CanvasLight {
+                    on: true,
+                    brightness: 128,
+                },
+            )
+            .await;
+
+        assert!(output.generation() > generation);
+        assert_eq!(
+            output.canvas_pipeline("kitchen").await.apply(WHITE),
+            [64; 3]
+        );
+        assert_eq!(output.canvas_pipeline("hall").await.apply(WHITE), [128; 3]);
+    }
+
+    #[tokio::test]
+    async fn switched_off_lights_blank_their_canvas() {
+        let output = output();
+
+        output
+            .set_light(
+                "kitchen".into(),
+                CanvasLight {
+                    on: false,
+                    brightness: 255,
+                },
+            )
+            .await;
+
+        assert_eq!(output.canvas_pipeline("kitchen").await.apply(WHITE), [0; 3]);
+        assert!(output.settings().await.on);
+    }
+}
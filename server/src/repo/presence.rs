@@ -0,0 +1,146 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use crate::{
+    repo::{
+        team::TeamScore,
+        voting::{Round, RoundResults},
+    },
+    types::Color,
+};
+
+const UPDATES_CAPACITY: usize = 256;
+
+/// Sent as text to websocket clients that opted into presence.
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PresenceMessage {
+    /// Clients connected to the canvas.
+    Presence { browsers: usize, devices: usize },
+    /// Where another client is pointing, `led` is `null` once it stopped.
+    Cursor {
+        client: Uuid,
+        led: Option<usize>,
+        color: Option<Color>,
+    },
+    /// Scores while team mode is on.
+    Scores { teams: Vec<TeamScore> },
+    /// The open round while voting mode is on, sent every second.
+    Round(Round),
+    /// What the round that just ended changed.
+    RoundResults(RoundResults),
+}
+
+/// Sent as text by websocket clients that opted into presence.
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PresenceCommand {
+    /// Shares the hovered led and picked color, `led` is `null` to hide.
+    Cursor {
+        led: Option<usize>,
+        #[serde(default)]
+        color: Option<Color>,
+    },
+}
+
+#[derive(Clone, Copy)]
+struct Cursor {
+    led: usize,
+    color: Option<Color>,
+}
+
+/// Live cursors of the clients of a canvas.
+#[derive(Clone)]
+pub struct PresenceRepo(Arc<PresenceRepoInner>);
+
+pub struct PresenceRepoInner {
+    cursors: Mutex<BTreeMap<Uuid, Cursor>>,
+    updates: broadcast::Sender<PresenceMessage>,
+}
+
+impl Default for PresenceRepo {
+    fn default() -> Self {
+        Self(Arc::new(PresenceRepoInner {
+            cursors: Mutex::new(BTreeMap::new()),
+            updates: broadcast::Sender::new(UPDATES_CAPACITY),
+        }))
+    }
+}
+
+impl PresenceRepo {
+    pub fn new() -> Self { Self::default() }
+
+    /// Updates from now on, along with where every cursor currently is.
+    pub fn subscribe(&self) -> (Vec<PresenceMessage>, broadcast::Receiver<PresenceMessage>) {
+        let cursors = self.0.cursors.lock().expect("cursors lock poisoned");
+        let current = cursors
+            .iter()
+            .map(|(client, cursor)| PresenceMessage::Cursor {
+                client: *client,
+                led: Some(cursor.led),
+                color: cursor.color,
+            })
+            .collect();
+
+        (current, self.0.updates.subscribe())
+    }
+
+    /// Sends `message` to every presence client of the canvas.
+    pub fn broadcast(&self, message: PresenceMessage) {
+        // Nobody listening isn't an error.
+        let _ = self.0.updates.send(message);
+    }
+
+    /// Lets `client` share its cursor, which disappears with the returned
+    /// handle.
+    pub fn join(&self, client: Uuid) -> CursorHandle {
+        CursorHandle {
+            repo: self.clone(),
+            client,
+        }
+    }
+
+    fn move_cursor(&self, client: Uuid, cursor: Option<Cursor>) {
+        let mut cursors = self.0.cursors.lock().expect("cursors lock poisoned");
+        let moved = match cursor {
+            Some(cursor) => {
+                cursors.insert(client, cursor);
+                true
+            }
+            None => cursors.remove(&client).is_some(),
+        };
+
+        // Sent under the lock so subscribers see moves in the same order as
+        // the cursors they started from.
+        if moved {
+            // Nobody listening isn't an error.
+            let _ = self.0.updates.send(PresenceMessage::Cursor {
+                client,
+                led: cursor.map(|cursor| cursor.led),
+                color: cursor.and_then(|cursor| cursor.color),
+            });
+        }
+    }
+}
+
+pub struct CursorHandle {
+    repo: PresenceRepo,
+    client: Uuid,
+}
+
+impl CursorHandle {
+    pub fn move_to(&self, led: Option<usize>, color: Option<Color>) {
+        self.repo
+            .move_cursor(self.client, led.map(|led| Cursor { led, color }))
+    }
+}
+
+impl Drop for CursorHandle {
+    fn drop(&mut self) { self.repo.move_cursor(self.client, None) }
+}
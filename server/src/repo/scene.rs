@@ -0,0 +1,27 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use tokio::sync::RwLock;
+
+use crate::types::Color;
+
+/// Named snapshots of a canvas' colors that can be re-applied later.
+#[derive(Clone, Default)]
+pub struct SceneRepo(Arc<RwLock<BTreeMap<String, Vec<Color>>>>);
+
+impl SceneRepo {
+    pub fn new() -> Self { Self::default() }
+
+    pub async fn names(&self) -> Vec<String> { self.0.read().await.keys().cloned().collect() }
+
+    pub async fn get(&self, name: &str) -> Option<Vec<Color>> {
+        self.0.read().await.get(name).cloned()
+    }
+
+    pub async fn save(&self, name: String, colors: Vec<Color>) {
+        self.0.write().await.insert(name, colors);
+    }
+
+    pub async fn remove(&self, name: &str) -> Option<Vec<Color>> {
+        self.0.write().await.remove(name)
+    }
+}
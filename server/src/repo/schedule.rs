@@ -0,0 +1,124 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use chrono::{DateTime, Duration, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use crate::{
+    solar::{solar_event_time, Coordinates, SolarEvent},
+    types::Color,
+};
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Trigger {
+    /// Fires every day at a fixed UTC time.
+    Time { at: NaiveTime },
+    /// Fires every day relative to a solar event, e.g. `-30` minutes for
+    /// "30 minutes before sunset".
+    Solar {
+        event: SolarEvent,
+        #[serde(default)]
+        offset_minutes: i64,
+    },
+}
+
+impl Trigger {
+    pub fn requires_coordinates(&self) -> bool { matches!(self, Trigger::Solar { .. }) }
+
+    /// Returns the first time this trigger fires strictly after `after`.
+    pub fn next_after(
+        &self,
+        after: DateTime<Utc>,
+        coordinates: Option<Coordinates>,
+    ) -> Option<DateTime<Utc>> {
+        // Solar events can land on the neighbouring UTC date depending on the
+        // longitude, so look a day behind as well as a couple of days ahead.
+        let start = after.date_naive() - Duration::days(1);
+
+        start
+            .iter_days()
+            .take(4)
+            .filter_map(|date| match *self {
+                Trigger::Time { at } => Some(date.and_time(at).and_utc()),
+                Trigger::Solar {
+                    event,
+                    offset_minutes,
+                } => solar_event_time(date, coordinates?, event)?
+                    .checked_add_signed(Duration::try_minutes(offset_minutes)?),
+            })
+            .find(|time| *time > after)
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Schedule {
+    pub trigger: Trigger,
+    pub color: Color,
+    /// Canvas to fill, or the default canvas when omitted.
+    #[serde(default)]
+    pub canvas: Option<String>,
+}
+
+#[derive(Clone, Default)]
+pub struct ScheduleRepo(Arc<RwLock<BTreeMap<Uuid, Schedule>>>);
+
+impl ScheduleRepo {
+    pub fn new() -> Self { Self::default() }
+
+    pub async fn list(&self) -> Vec<(Uuid, Schedule)> {
+        self.0
+            .read()
+            .await
+            .iter()
+            .map(|(id, schedule)| (*id, schedule.clone()))
+            .collect()
+    }
+
+    pub async fn insert(&self, schedule: Schedule) -> Uuid {
+        let id = Uuid::new_v4();
+        self.0.write().await.insert(id, schedule);
+        id
+    }
+
+    pub async fn remove(&self, id: Uuid) -> Option<Schedule> { self.0.write().await.remove(&id) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LONDON: Coordinates = Coordinates {
+        latitude: 51.5074,
+        longitude: -0.1278,
+    };
+
+    #[test]
+    fn solar_offsets_shift_the_event() {
+        let after = "2024-06-20T12:00:00Z".parse().unwrap();
+        let sunset = Trigger::Solar {
+            event: SolarEvent::Sunset,
+            offset_minutes: 0,
+        };
+        let before_sunset = Trigger::Solar {
+            event: SolarEvent::Sunset,
+            offset_minutes: -30,
+        };
+
+        let sunset = sunset.next_after(after, Some(LONDON)).unwrap();
+        let before_sunset = before_sunset.next_after(after, Some(LONDON)).unwrap();
+
+        assert_eq!(sunset - before_sunset, Duration::minutes(30));
+    }
+
+    #[test]
+    fn out_of_range_offsets_never_fire() {
+        let trigger = Trigger::Solar {
+            event: SolarEvent::Sunset,
+            offset_minutes: i64::MAX,
+        };
+
+        assert_eq!(trigger.next_after(Utc::now(), Some(LONDON)), None);
+    }
+}
@@ -0,0 +1,249 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    net::IpAddr,
+    sync::{Arc, RwLock},
+};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use rand::random;
+use serde::Serialize;
+use uuid::Uuid;
+
+/// Sessions are kept in memory, so they end with the process or after this
+/// long, whichever comes first.
+pub const SESSION_LIFETIME: TimeDelta = TimeDelta::days(30);
+/// Starting a session beyond this ends the oldest one.
+const MAX_SESSIONS: usize = 100_000;
+/// Sessions a single client may start per [`SESSION_RATE_WINDOW`], since
+/// every session gets its own votes.
+const SESSIONS_PER_CLIENT: usize = 10;
+const SESSION_RATE_WINDOW: TimeDelta = TimeDelta::minutes(10);
+
+/// An anonymous visitor. The `id` is public and attributed to their edits,
+/// while the token proving they own the session stays with them.
+#[derive(Serialize, Clone, Debug)]
+pub struct Session {
+    pub id: Uuid,
+    pub nickname: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    /// Set once the session joins a team, see [`super::team::TeamRepo`].
+    pub team: Option<Arc<str>>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum SessionRepoError {
+    #[error("Session with id {0} does not exist")]
+    NotFound(Uuid),
+    #[error("Already on team '{0}'")]
+    AlreadyInTeam(Arc<str>),
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("Too many sessions started from this address, try again later")]
+pub struct TooManySessions;
+
+#[derive(Clone, Default)]
+pub struct SessionRepo(Arc<RwLock<SessionRepoInner>>);
+
+#[derive(Default)]
+pub struct SessionRepoInner {
+    tokens: HashMap<String, Uuid>,
+    sessions: HashMap<Uuid, Session>,
+    /// Tokens from the oldest session to the newest, which is also the order
+    /// they expire in.
+    order: VecDeque<String>,
+    /// Sessions started per client since `window_start`.
+    started: HashMap<IpAddr, usize>,
+    window_start: DateTime<Utc>,
+}
+
+impl SessionRepo {
+    pub fn new() -> Self { Self::default() }
+
+    /// Starts a session for `client`, returning it along with its token.
+    pub fn create(
+        &self,
+        nickname: Option<String>,
+        client: IpAddr,
+    ) -> Result<(Session, String), TooManySessions> {
+        let created_at = Utc::now();
+        let mut inner = self.0.write().expect("sessions lock poisoned");
+        inner.count_start(client, created_at)?;
+
+        let session = Session {
+            id: Uuid::new_v4(),
+            nickname,
+            created_at,
+            expires_at: created_at + SESSION_LIFETIME,
+            team: None,
+        };
+        let token = hex::encode(random::<[u8; 32]>());
+
+        inner.end_expired(created_at, MAX_SESSIONS - 1);
+        inner.tokens.insert(token.clone(), session.id);
+        inner.sessions.insert(session.id, session.clone());
+        inner.order.push_back(token.clone());
+
+        Ok((session, token))
+    }
+
+    pub fn get(&self, id: Uuid) -> Option<Session> {
+        self.0
+            .read()
+            .expect("sessions lock poisoned")
+            .sessions
+            .get(&id)
+            .filter(|session| session.expires_at > Utc::now())
+            .cloned()
+    }
+
+    pub fn by_token(&self, token: &str) -> Option<Session> {
+        let inner = self.0.read().expect("sessions lock poisoned");
+        let id = inner.tokens.get(token)?;
+
+        inner
+            .sessions
+            .get(id)
+            .filter(|session| session.expires_at > Utc::now())
+            .cloned()
+    }
+
+    pub fn set_nickname(&self, id: Uuid, nickname: Option<String>) -> Option<Session> {
+        let mut inner = self.0.write().expect("sessions lock poisoned");
+        let session = inner.sessions.get_mut(&id)?;
+        session.nickname = nickname;
+
+        Some(session.clone())
+    }
+
+    pub fn nickname(&self, id: Uuid) -> Option<String> { self.get(id)?.nickname }
+
+    /// Puts the session on `team` for good, rejoining the same team is fine.
+    pub fn join_team(&self, id: Uuid, team: Arc<str>) -> Result<Session, SessionRepoError> {
+        let mut inner = self.0.write().expect("sessions lock poisoned");
+        let session = inner
+            .sessions
+            .get_mut(&id)
+            .ok_or(SessionRepoError::NotFound(id))?;
+
+        match &session.team {
+            Some(current) if *current != team => {
+                Err(SessionRepoError::AlreadyInTeam(current.clone()))
+            }
+            _ => {
+                session.team = Some(team);
+                Ok(session.clone())
+            }
+        }
+    }
+
+    /// The team of every live session that joined one.
+    pub fn teams(&self) -> HashMap<Uuid, Arc<str>> {
+        self.0
+            .read()
+            .expect("sessions lock poisoned")
+            .sessions
+            .values()
+            .filter(|session| session.expires_at > Utc::now())
+            .filter_map(|session| Some((session.id, session.team.clone()?)))
+            .collect()
+    }
+}
+
+impl SessionRepoInner {
+    /// Counts a session started by `client`, unless it already started too
+    /// many in the current window.
+    fn count_start(&mut self, client: IpAddr, now: DateTime<Utc>) -> Result<(), TooManySessions> {
+        if now - self.window_start >= SESSION_RATE_WINDOW {
+            self.started.clear();
+            self.window_start = now;
+        }
+
+        let started = self.started.entry(client).or_default();
+        if *started >= SESSIONS_PER_CLIENT {
+            return Err(TooManySessions);
+        }
+        *started += 1;
+
+        Ok(())
+    }
+
+    /// Ends the sessions that expired by `now`, then the oldest ones until at
+    /// most `keep` are left.
+    fn end_expired(&mut self, now: DateTime<Utc>, keep: usize) {
+        while let Some(token) = self.order.front() {
+            let id = self.tokens.get(token).copied();
+            let expired = id
+                .and_then(|id| self.sessions.get(&id))
+                .is_none_or(|session| session.expires_at <= now);
+            if !expired && self.order.len() <= keep {
+                break;
+            }
+
+            if let Some(token) = self.order.pop_front() {
+                self.tokens.remove(&token);
+            }
+            if let Some(id) = id {
+                self.sessions.remove(&id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    const CLIENT: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+    #[test]
+    fn expired_sessions_are_not_found() {
+        let sessions = SessionRepo::new();
+        let (session, token) = sessions.create(None, CLIENT).unwrap();
+        assert!(sessions.by_token(&token).is_some());
+
+        let mut inner = sessions.0.write().unwrap();
+        inner.sessions.get_mut(&session.id).unwrap().expires_at = Utc::now();
+        drop(inner);
+
+        assert!(sessions.by_token(&token).is_none());
+        assert!(sessions.get(session.id).is_none());
+    }
+
+    #[test]
+    fn ending_sessions_drops_expired_then_oldest() {
+        let sessions = SessionRepo::new();
+        let created: Vec<_> = (0..4)
+            .map(|_| sessions.create(None, CLIENT).unwrap())
+            .collect();
+
+        let mut inner = sessions.0.write().unwrap();
+        // Only the second one expired, the first is the oldest left.
+        inner.sessions.get_mut(&created[1].0.id).unwrap().expires_at = Utc::now();
+        inner.end_expired(Utc::now(), 2);
+
+        let left: Vec<_> = inner.order.iter().cloned().collect();
+        assert_eq!(left, [created[2].1.clone(), created[3].1.clone()]);
+        assert_eq!(inner.sessions.len(), 2);
+        assert_eq!(inner.tokens.len(), 2);
+    }
+
+    #[test]
+    fn clients_can_only_start_so_many_sessions() {
+        let sessions = SessionRepo::new();
+        let other = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+
+        for _ in 0..SESSIONS_PER_CLIENT {
+            assert!(sessions.create(None, CLIENT).is_ok());
+        }
+        assert!(sessions.create(None, CLIENT).is_err());
+        assert!(sessions.create(None, other).is_ok());
+
+        // A new window starts the count over.
+        sessions.0.write().unwrap().window_start -= SESSION_RATE_WINDOW;
+        assert!(sessions.create(None, CLIENT).is_ok());
+    }
+}
@@ -0,0 +1,160 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde::Serialize;
+
+use crate::{
+    config::TeamConfig,
+    repo::session::Session,
+    types::{Color, HexColor},
+};
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Team {
+    pub name: Arc<str>,
+    /// The only colors members may paint with, the first one is the team's
+    /// own.
+    pub palette: Vec<HexColor>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct TeamScore {
+    pub team: Arc<str>,
+    pub members: usize,
+    /// Leds whose latest single-led write came from a member.
+    pub leds_owned: usize,
+    /// Leds owned integrated over time, so holding 10 leds for a minute
+    /// scores 600.
+    pub led_seconds_held: f64,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum TeamError {
+    #[error("Join a team to paint during team mode")]
+    NoTeam,
+    #[error("Team '{0}' can only paint with its palette")]
+    OffPalette(Arc<str>),
+    #[error("Only single leds can be painted during team mode")]
+    Bulk,
+}
+
+/// Teams competing for a canvas' leds. Team mode is off without any team.
+#[derive(Clone)]
+pub struct TeamRepo(Arc<TeamRepoInner>);
+
+pub struct TeamRepoInner {
+    canvas: Arc<str>,
+    teams: Vec<Team>,
+    scores: Mutex<Vec<TeamScore>>,
+}
+
+impl TeamRepo {
+    pub fn new(configs: &[TeamConfig], canvas: Arc<str>) -> Self {
+        let teams: Vec<Team> = configs
+            .iter()
+            .map(|config| Team {
+                name: config.name.as_str().into(),
+                palette: config.palette.iter().copied().map(HexColor).collect(),
+            })
+            .collect();
+        let scores = teams
+            .iter()
+            .map(|team| TeamScore {
+                team: team.name.clone(),
+                members: 0,
+                leds_owned: 0,
+                led_seconds_held: 0.0,
+            })
+            .collect();
+
+        Self(Arc::new(TeamRepoInner {
+            canvas,
+            teams,
+            scores: Mutex::new(scores),
+        }))
+    }
+
+    pub fn is_active(&self) -> bool { !self.0.teams.is_empty() }
+
+    /// The canvas played on.
+    pub fn canvas(&self) -> &str { &self.0.canvas }
+
+    pub fn teams(&self) -> &[Team] { &self.0.teams }
+
+    pub fn get(&self, name: &str) -> Option<&Team> {
+        self.0.teams.iter().find(|team| &*team.name == name)
+    }
+
+    /// While team mode is on, only members may paint the played canvas and
+    /// only with their team's palette.
+    pub fn check_write(
+        &self,
+        canvas: &str,
+        session: Option<&Session>,
+        color: Color,
+    ) -> Result<(), TeamError> {
+        if !self.is_active() || canvas != self.canvas() {
+            return Ok(());
+        }
+
+        let team = session
+            .and_then(|session| session.team.as_deref())
+            .and_then(|team| self.get(team))
+            .ok_or(TeamError::NoTeam)?;
+
+        if !team.palette.contains(&HexColor(color)) {
+            return Err(TeamError::OffPalette(team.name.clone()));
+        }
+
+        Ok(())
+    }
+
+    /// Bulk writes would wipe out the teams' leds, so they're refused on the
+    /// played canvas while team mode is on.
+    pub fn check_bulk_write(&self, canvas: &str) -> Result<(), TeamError> {
+        if self.is_active() && canvas == self.canvas() {
+            return Err(TeamError::Bulk);
+        }
+
+        Ok(())
+    }
+
+    pub fn scores(&self) -> Vec<TeamScore> {
+        self.0.scores.lock().expect("scores lock poisoned").clone()
+    }
+
+    /// Scores from the best team to the worst, by leds held over time and
+    /// then by leds currently owned.
+    pub fn leaderboard(&self) -> Vec<TeamScore> {
+        let mut scores = self.scores();
+        scores.sort_by(|a, b| {
+            b.led_seconds_held
+                .total_cmp(&a.led_seconds_held)
+                .then(b.leds_owned.cmp(&a.leds_owned))
+        });
+
+        scores
+    }
+
+    /// Records what every team owned for the last `elapsed`, returning the
+    /// new scores.
+    pub fn record(
+        &self,
+        leds_owned: &HashMap<Arc<str>, usize>,
+        members: &HashMap<Arc<str>, usize>,
+        elapsed: Duration,
+    ) -> Vec<TeamScore> {
+        let mut scores = self.0.scores.lock().expect("scores lock poisoned");
+
+        for score in scores.iter_mut() {
+            score.members = members.get(&score.team).copied().unwrap_or_default();
+            score.leds_owned = leds_owned.get(&score.team).copied().unwrap_or_default();
+            score.led_seconds_held += score.leds_owned as f64 * elapsed.as_secs_f64();
+        }
+
+        scores.clone()
+    }
+}
@@ -0,0 +1,263 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::types::Color;
+
+/// The round currently open for votes.
+#[derive(Serialize, Clone, Debug)]
+pub struct Round {
+    pub number: u64,
+    pub started_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    pub voters: usize,
+    pub votes: usize,
+}
+
+/// The color a led got at the end of a round.
+#[derive(Serialize, Clone, Copy, Debug)]
+pub struct LedResult {
+    pub led: usize,
+    pub color: Color,
+    pub votes: usize,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct RoundResults {
+    pub number: u64,
+    pub ended_at: DateTime<Utc>,
+    pub voters: usize,
+    /// Only the leds that got votes, in led order.
+    pub leds: Vec<LedResult>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum VotingError {
+    #[error("Leds can only be voted on during voting mode")]
+    DirectWrite,
+}
+
+/// Rounds of votes deciding the next state of a canvas. Voting mode is off
+/// without a canvas.
+#[derive(Clone)]
+pub struct VotingRepo(Arc<VotingRepoInner>);
+
+pub struct VotingRepoInner {
+    canvas: Option<Arc<str>>,
+    round_duration: TimeDelta,
+    state: Mutex<VotingState>,
+}
+
+struct VotingState {
+    number: u64,
+    started_at: DateTime<Utc>,
+    /// Every led's votes along with when they were cast, a voter changing
+    /// their mind casts a new vote.
+    votes: BTreeMap<usize, HashMap<Uuid, (u64, Color)>>,
+    /// Everyone who voted this round.
+    voters: HashSet<Uuid>,
+    vote_count: usize,
+    /// Sequence number of the next vote, only ever compared within a round.
+    next_vote: u64,
+    results: Option<RoundResults>,
+}
+
+impl VotingRepo {
+    pub fn new(canvas: Option<Arc<str>>, round_duration: Duration) -> Self {
+        Self(Arc::new(VotingRepoInner {
+            canvas,
+            round_duration: TimeDelta::from_std(round_duration)
+                .expect("round duration out of range"),
+            state: Mutex::new(VotingState {
+                number: 1,
+                started_at: Utc::now(),
+                votes: BTreeMap::new(),
+                voters: HashSet::new(),
+                vote_count: 0,
+                next_vote: 0,
+                results: None,
+            }),
+        }))
+    }
+
+    pub fn is_active(&self) -> bool { self.0.canvas.is_some() }
+
+    /// The canvas voted on.
+    pub fn canvas(&self) -> Option<&str> { self.0.canvas.as_deref() }
+
+    /// While voting mode is on, the voted canvas only changes at the end of
+    /// rounds.
+    pub fn check_write(&self, canvas: &str) -> Result<(), VotingError> {
+        if self.canvas() == Some(canvas) {
+            return Err(VotingError::DirectWrite);
+        }
+
+        Ok(())
+    }
+
+    pub fn round(&self) -> Round { self.0.round(&self.lock()) }
+
+    /// Results of the latest round that ended.
+    pub fn results(&self) -> Option<RoundResults> { self.lock().results.clone() }
+
+    /// Votes for `color` on `led` of the voted canvas, replacing the voter's
+    /// previous vote on it.
+    pub fn vote(&self, voter: Uuid, led: usize, color: Color) -> Round {
+        let mut state = self.lock();
+        let sequence = state.next_vote;
+        state.next_vote += 1;
+        let replaced = state
+            .votes
+            .entry(led)
+            .or_default()
+            .insert(voter, (sequence, color));
+        if replaced.is_none() {
+            state.vote_count += 1;
+            state.voters.insert(voter);
+        }
+
+        self.0.round(&state)
+    }
+
+    /// Tallies the round and opens the next one. Every led voted on gets its
+    /// most voted color, ties going to the color voted for first.
+    pub fn end_round(&self) -> RoundResults {
+        let mut state = self.lock();
+        let votes = std::mem::take(&mut state.votes);
+        let voters = std::mem::take(&mut state.voters);
+        state.vote_count = 0;
+        state.next_vote = 0;
+
+        let leds = votes
+            .iter()
+            .filter_map(|(led, votes)| {
+                // Every color with its votes and its earliest vote.
+                let mut tally: HashMap<[u8; 3], (Color, usize, u64)> = HashMap::new();
+                for (sequence, color) in votes.values() {
+                    let (_, count, first) = tally
+                        .entry([color.red, color.green, color.blue])
+                        .or_insert((*color, 0, *sequence));
+                    *count += 1;
+                    *first = (*first).min(*sequence);
+                }
+
+                let (color, votes, _) = tally
+                    .into_values()
+                    .max_by_key(|(_, count, first)| (*count, Reverse(*first)))?;
+
+                Some(LedResult {
+                    led: *led,
+                    color,
+                    votes,
+                })
+            })
+            .collect();
+
+        let results = RoundResults {
+            number: state.number,
+            ended_at: Utc::now(),
+            voters: voters.len(),
+            leds,
+        };
+
+        state.number += 1;
+        state.started_at = results.ended_at;
+        state.results = Some(results.clone());
+
+        results
+    }
+
+    fn lock(&self) -> MutexGuard<'_, VotingState> {
+        self.0.state.lock().expect("voting lock poisoned")
+    }
+}
+
+impl VotingRepoInner {
+    fn round(&self, state: &VotingState) -> Round {
+        Round {
+            number: state.number,
+            started_at: state.started_at,
+            ends_at: state.started_at + self.round_duration,
+            voters: state.voters.len(),
+            votes: state.vote_count,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: Color = Color {
+        red: 255,
+        green: 0,
+        blue: 0,
+    };
+    const BLUE: Color = Color {
+        red: 0,
+        green: 0,
+        blue: 255,
+    };
+
+    fn voting() -> VotingRepo { VotingRepo::new(Some("default".into()), Duration::from_secs(30)) }
+
+    #[test]
+    fn most_voted_color_wins() {
+        let voting = voting();
+        voting.vote(Uuid::new_v4(), 0, RED);
+        voting.vote(Uuid::new_v4(), 0, BLUE);
+        voting.vote(Uuid::new_v4(), 0, BLUE);
+
+        let results = voting.end_round();
+
+        assert_eq!(results.leds.len(), 1);
+        assert_eq!(results.leds[0].color, BLUE);
+        assert_eq!(results.leds[0].votes, 2);
+        assert_eq!(results.voters, 3);
+    }
+
+    #[test]
+    fn ties_go_to_the_color_voted_for_first() {
+        let voting = voting();
+        let voters: Vec<Uuid> = (0..4).map(|_| Uuid::new_v4()).collect();
+        voting.vote(voters[0], 3, BLUE);
+        voting.vote(voters[1], 3, RED);
+        voting.vote(voters[2], 3, RED);
+        voting.vote(voters[3], 3, BLUE);
+
+        let results = voting.end_round();
+        assert_eq!(results.leds[0].color, BLUE);
+        assert_eq!(results.leds[0].votes, 2);
+
+        voting.vote(voters[0], 3, BLUE);
+        voting.vote(voters[1], 3, RED);
+        voting.vote(voters[2], 3, RED);
+        voting.vote(voters[3], 3, BLUE);
+        // Voting again, even for the same color, goes to the back.
+        voting.vote(voters[0], 3, BLUE);
+
+        assert_eq!(voting.end_round().leds[0].color, RED);
+    }
+
+    #[test]
+    fn changing_a_vote_replaces_it() {
+        let voting = voting();
+        let voter = Uuid::new_v4();
+        voting.vote(voter, 0, RED);
+        let round = voting.vote(voter, 0, BLUE);
+
+        assert_eq!((round.votes, round.voters), (1, 1));
+
+        let results = voting.end_round();
+        assert_eq!(results.leds[0].color, BLUE);
+        assert_eq!(results.leds[0].votes, 1);
+        assert_eq!((voting.round().votes, voting.round().voters), (0, 0));
+    }
+}
@@ -0,0 +1,258 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::Arc,
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use crate::events::{AppEvent, EventKind};
+
+/// How many deliveries are kept per webhook for the delivery log.
+const DELIVERY_LOG_SIZE: usize = 100;
+
+/// Half-open range of led ids, `start..end`.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug)]
+pub struct LedRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Webhook {
+    pub url: String,
+    /// Key for the HMAC-SHA256 signature sent with every delivery.
+    #[serde(skip_serializing)]
+    pub secret: String,
+    /// Events to deliver, all of them when empty.
+    #[serde(default)]
+    pub events: Vec<EventKind>,
+    /// Only deliver events of this canvas.
+    #[serde(default)]
+    pub canvas: Option<String>,
+    /// Only deliver led changes inside this range.
+    #[serde(default)]
+    pub led_range: Option<LedRange>,
+}
+
+impl Webhook {
+    /// Narrows `event` down to what this webhook subscribed to, if anything.
+    pub fn filter(&self, event: &AppEvent) -> Option<AppEvent> {
+        if !self.events.is_empty() && !self.events.contains(&event.kind()) {
+            return None;
+        }
+
+        if self
+            .canvas
+            .as_deref()
+            .is_some_and(|canvas| canvas != event.canvas())
+        {
+            return None;
+        }
+
+        match (event, self.led_range) {
+            (
+                AppEvent::LedsChanged {
+                    canvas,
+                    generation,
+                    leds,
+                },
+                Some(LedRange { start, end }),
+            ) => {
+                let leds: Vec<_> = leds
+                    .iter()
+                    .filter(|led| (start..end).contains(&led.id))
+                    .copied()
+                    .collect();
+
+                (!leds.is_empty()).then(|| AppEvent::LedsChanged {
+                    canvas: canvas.clone(),
+                    generation: *generation,
+                    leds,
+                })
+            }
+            _ => Some(event.clone()),
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Delivery {
+    pub id: Uuid,
+    pub events: Vec<EventKind>,
+    pub attempts: u32,
+    pub status: Option<u16>,
+    pub error: Option<String>,
+    pub success: bool,
+    pub finished_at: DateTime<Utc>,
+}
+
+struct WebhookEntry {
+    webhook: Webhook,
+    deliveries: VecDeque<Delivery>,
+}
+
+struct WebhookRepoInner {
+    webhooks: RwLock<BTreeMap<Uuid, WebhookEntry>>,
+    allow_private_targets: bool,
+}
+
+#[derive(Clone)]
+pub struct WebhookRepo(Arc<WebhookRepoInner>);
+
+impl WebhookRepo {
+    pub fn new(allow_private_targets: bool) -> Self {
+        Self(Arc::new(WebhookRepoInner {
+            webhooks: RwLock::default(),
+            allow_private_targets,
+        }))
+    }
+
+    /// Whether webhooks may deliver to loopback, private and link-local
+    /// addresses.
+    pub fn allows_private_targets(&self) -> bool { self.0.allow_private_targets }
+
+    pub async fn list(&self) -> Vec<(Uuid, Webhook)> {
+        self.0
+            .webhooks
+            .read()
+            .await
+            .iter()
+            .map(|(id, entry)| (*id, entry.webhook.clone()))
+            .collect()
+    }
+
+    pub async fn insert(&self, webhook: Webhook) -> Uuid {
+        let id = Uuid::new_v4();
+        self.0.webhooks.write().await.insert(
+            id,
+            WebhookEntry {
+                webhook,
+                deliveries: VecDeque::new(),
+            },
+        );
+        id
+    }
+
+    pub async fn remove(&self, id: Uuid) -> Option<Webhook> {
+        self.0
+            .webhooks
+            .write()
+            .await
+            .remove(&id)
+            .map(|entry| entry.webhook)
+    }
+
+    pub async fn deliveries(&self, id: Uuid) -> Option<Vec<Delivery>> {
+        self.0
+            .webhooks
+            .read()
+            .await
+            .get(&id)
+            .map(|entry| entry.deliveries.iter().cloned().collect())
+    }
+
+    /// Records a finished delivery, forgetting the oldest one once the log is
+    /// full. Deliveries for webhooks removed in the meantime are dropped.
+    pub async fn record_delivery(&self, id: Uuid, delivery: Delivery) {
+        if let Some(entry) = self.0.webhooks.write().await.get_mut(&id) {
+            if entry.deliveries.len() == DELIVERY_LOG_SIZE {
+                entry.deliveries.pop_front();
+            }
+            entry.deliveries.push_back(delivery);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{events::ChangedLed, types::Color};
+
+    fn webhook() -> Webhook {
+        Webhook {
+            url: "https://example.com/".to_string(),
+            secret: "secret".to_string(),
+            events: vec![],
+            canvas: None,
+            led_range: None,
+        }
+    }
+
+    fn leds_changed(canvas: &str, ids: &[usize]) -> AppEvent {
+        AppEvent::LedsChanged {
+            canvas: canvas.into(),
+            generation: 1,
+            leds: ids
+                .iter()
+                .map(|&id| ChangedLed {
+                    id,
+                    color: Color {
+                        red: 0,
+                        green: 0,
+                        blue: 0,
+                    },
+                    last_updated: Utc::now(),
+                    painted_by: None,
+                })
+                .collect(),
+        }
+    }
+
+    fn led_ids(event: Option<AppEvent>) -> Vec<usize> {
+        match event {
+            Some(AppEvent::LedsChanged { leds, .. }) => leds.iter().map(|led| led.id).collect(),
+            _ => panic!("expected led changes"),
+        }
+    }
+
+    #[test]
+    fn filter_by_kind() {
+        let webhook = Webhook {
+            events: vec![EventKind::SceneApplied],
+            ..webhook()
+        };
+        let scene = AppEvent::SceneApplied {
+            canvas: "default".into(),
+            scene: "scene".to_string(),
+        };
+
+        assert!(webhook.filter(&scene).is_some());
+        assert!(webhook.filter(&leds_changed("default", &[0])).is_none());
+    }
+
+    #[test]
+    fn filter_by_canvas() {
+        let webhook = Webhook {
+            canvas: Some("kitchen".to_string()),
+            ..webhook()
+        };
+
+        assert!(webhook.filter(&leds_changed("kitchen", &[0])).is_some());
+        assert!(webhook.filter(&leds_changed("default", &[0])).is_none());
+    }
+
+    #[test]
+    fn filter_narrows_led_changes_to_the_range() {
+        let webhook = Webhook {
+            led_range: Some(LedRange { start: 2, end: 4 }),
+            ..webhook()
+        };
+
+        assert_eq!(
+            led_ids(webhook.filter(&leds_changed("default", &[1, 2, 3, 4]))),
+            vec![2, 3]
+        );
+        assert!(webhook.filter(&leds_changed("default", &[0, 4])).is_none());
+    }
+
+    #[test]
+    fn empty_filter_passes_everything() {
+        assert_eq!(
+            led_ids(webhook().filter(&leds_changed("default", &[0, 1]))),
+            vec![0, 1]
+        );
+    }
+}
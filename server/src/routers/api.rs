@@ -1,4 +1,8 @@
-use std::{sync::Arc, time::Duration};
+use std::{
+    collections::{BTreeSet, VecDeque},
+    sync::{Arc, LazyLock},
+    time::Duration,
+};
 
 use axum::{
     body::Bytes,
@@ -6,35 +10,83 @@ use axum::{
         ws::{Message, WebSocket},
         Path, Query, State, WebSocketUpgrade,
     },
-    http::StatusCode,
-    response::IntoResponse,
+    http::{header, HeaderMap, HeaderName, StatusCode},
+    response::{IntoResponse, Response},
     routing::get,
     Form, Json, Router,
 };
 use axum_client_ip::InsecureClientIp;
 use axum_thiserror::ErrorStatus;
-use futures::{
-    stream::{SplitSink, SplitStream},
-    SinkExt, StreamExt,
-};
+use chrono::Utc;
+use futures::{stream::SplitStream, StreamExt};
 use serde::{Deserialize, Serialize};
 use serde_inline_default::serde_inline_default;
-use tokio::{spawn, sync::Mutex, time::sleep};
-use tracing::{error, info, info_span, Instrument};
+use serde_json::json;
+use tokio::{
+    spawn,
+    sync::{broadcast::error::RecvError, watch},
+    time::{interval, interval_at, sleep, sleep_until, Instant},
+};
+use tracing::{error, info, info_span, warn, Instrument};
 use uuid::Uuid;
 
+use super::{
+    canvas, client,
+    device::{self, DeviceRouterError},
+    effect, image, layout,
+    outbox::Outbox,
+    output, scene, schedule, session, sse, team, voting, webhook,
+};
 use crate::{
-    repo::led::{Led, LedRepo, LedRepoError},
+    gate::{WriteError, WriteGate},
+    output::websocket::{DeviceFrame, DeviceFrames},
+    palette::{apply_palette, PaletteError},
+    pipeline::ChannelOrder,
+    repo::{
+        canvas::Canvas,
+        client::ClientRepo,
+        device::{DeviceConfig, DeviceHandle, DeviceInfo, DeviceRepo},
+        led::{Led, LedRepo, LedRepoError},
+        output::OutputRepo,
+        presence::{CursorHandle, PresenceCommand, PresenceMessage, PresenceRepo},
+        session::{Session, SessionRepo},
+        team::TeamError,
+        voting::{VotingError, VotingRepo},
+    },
     state::AppState,
     types::Color,
 };
 
 pub fn get_router() -> Router<AppState> {
+    Router::new()
+        .merge(get_canvas_router())
+        .nest("/canvases/{canvas}", get_canvas_router())
+        .merge(canvas::get_router())
+        .merge(schedule::get_router())
+        .merge(output::get_router())
+        .merge(webhook::get_router())
+        .merge(device::get_router())
+        .merge(client::get_router())
+        .merge(session::get_router())
+        .merge(team::get_router())
+        .merge(voting::get_router())
+        .fallback(handler_404)
+}
+
+/// Routes served both for the default canvas at the root and for every
+/// canvas under `/canvases/{canvas}`.
+fn get_canvas_router() -> Router<AppState> {
     Router::new()
         .route("/leds", get(get_leds))
         .route("/leds/{id}", get(get_led).post(post_led))
         .route("/leds/ws", get(get_ws))
-        .fallback(handler_404)
+        .route("/leds/events", get(sse::get_events))
+        .route("/output/power", get(output::get_output_power))
+        .route("/palette", get(canvas::get_palette))
+        .merge(layout::get_router())
+        .merge(scene::get_router())
+        .merge(image::get_router())
+        .merge(effect::get_router())
 }
 
 async fn handler_404() -> StatusCode { StatusCode::NOT_FOUND }
@@ -44,48 +96,145 @@ pub enum LedRouterError {
     #[error("Led with id {0} does not exist")]
     #[status(StatusCode::NOT_FOUND)]
     NotFound(usize),
+    #[error("Led {0} is currently controlled by another source")]
+    #[status(StatusCode::LOCKED)]
+    Held(usize),
+    #[error(transparent)]
+    #[status(StatusCode::FORBIDDEN)]
+    Team(#[from] TeamError),
+    #[error(transparent)]
+    #[status(StatusCode::FORBIDDEN)]
+    Voting(#[from] VotingError),
+    #[error(transparent)]
+    #[status(StatusCode::UNPROCESSABLE_ENTITY)]
+    Palette(#[from] PaletteError),
+}
+
+#[derive(Deserialize)]
+struct LedPath {
+    id: usize,
+}
+
+#[derive(Serialize)]
+struct LedResponse {
+    #[serde(flatten)]
+    led: Led,
+    /// Nickname of the `painted_by` session, if it has one.
+    painted_by_nickname: Option<String>,
 }
 
 async fn get_led(
-    State(leds): State<LedRepo>,
-    Path(id): Path<usize>,
-) -> Result<Json<Led>, LedRouterError> {
+    Canvas { leds, .. }: Canvas,
+    State(sessions): State<SessionRepo>,
+    Path(LedPath { id }): Path<LedPath>,
+) -> Result<Json<LedResponse>, LedRouterError> {
     let led = leds.get(id).await.ok_or(LedRouterError::NotFound(id))?;
+    let painted_by_nickname = led.painted_by.and_then(|id| sessions.nickname(id));
 
-    Ok(Json(led))
+    Ok(Json(LedResponse {
+        led,
+        painted_by_nickname,
+    }))
 }
 
 #[derive(Serialize)]
-struct WithId<T: Serialize> {
+pub(super) struct WithId<T: Serialize> {
     id: usize,
     #[serde(flatten)]
     inner: T,
 }
 
 impl<T: Serialize> WithId<T> {
-    fn new(id: usize, inner: T) -> Self { Self { id, inner } }
+    pub(super) fn new(id: usize, inner: T) -> Self { Self { id, inner } }
 }
 
-async fn get_leds(State(leds): State<LedRepo>) -> Json<Vec<WithId<Led>>> {
-    Json(
-        leds.snapshot()
-            .await
-            .leds
-            .into_iter()
-            .enumerate()
-            .map(|(id, led)| WithId::new(id, led))
-            .collect(),
-    )
+const MAX_LONG_POLL_TIMEOUT: u64 = 60_000;
+const DEVICE_PING_INTERVAL: Duration = Duration::from_secs(10);
+const PRESENCE_INTERVAL: Duration = Duration::from_secs(2);
+/// Cursors move at most this often per client, the latest move going out once
+/// the interval is over.
+const CURSOR_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Generations restart at 0 with the process, so ETags and SSE event ids also
+/// name the process to keep clients from matching one from before a restart.
+pub(super) static BOOT_ID: LazyLock<Uuid> = LazyLock::new(Uuid::new_v4);
+
+#[serde_inline_default]
+#[derive(Deserialize)]
+struct GetLedsParams {
+    /// Long-poll until the canvas reaches this generation (or the timeout hits).
+    #[serde(default)]
+    wait_for_generation: Option<usize>,
+    /// Long-poll timeout in milliseconds.
+    #[serde_inline_default(30_000)]
+    timeout: u64,
+}
+
+/// Responds with the leds and an `ETag` derived from the process and
+/// generation, or `304` when it matches `If-None-Match`.
+async fn get_leds(
+    Canvas { leds, .. }: Canvas,
+    Query(GetLedsParams {
+        wait_for_generation,
+        timeout,
+    }): Query<GetLedsParams>,
+    headers: HeaderMap,
+) -> Response {
+    if let Some(generation) = wait_for_generation {
+        let timeout = Duration::from_millis(timeout.min(MAX_LONG_POLL_TIMEOUT));
+        // Timing out isn't an error, the client just gets the current state.
+        let _ = tokio::time::timeout(timeout, leds.wait_for_generation(generation)).await;
+    }
+
+    let snapshot = leds.snapshot().await;
+    let etag = format!("\"{}-{}\"", *BOOT_ID, snapshot.generation);
+    let generation_headers = [
+        (header::ETAG, etag.clone()),
+        (
+            HeaderName::from_static("x-led-generation"),
+            snapshot.generation.to_string(),
+        ),
+    ];
+
+    let not_modified = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            value
+                .split(',')
+                .any(|tag| tag.trim() == etag || tag.trim() == "*")
+        });
+
+    if not_modified {
+        return (StatusCode::NOT_MODIFIED, generation_headers).into_response();
+    }
+
+    let body: Vec<WithId<Led>> = snapshot
+        .leds
+        .into_iter()
+        .enumerate()
+        .map(|(id, led)| WithId::new(id, led))
+        .collect();
+
+    (generation_headers, Json(body)).into_response()
 }
 
 async fn post_led(
-    State(leds): State<LedRepo>,
-    Path(id): Path<usize>,
+    canvas: Canvas,
+    State(gate): State<WriteGate>,
+    Path(LedPath { id }): Path<LedPath>,
+    session: Option<Session>,
     Form(color): Form<Color>,
 ) -> Result<Json<Led>, LedRouterError> {
-    let led = leds.set(id, color).await.map_err(|err| match err {
-        LedRepoError::OutOfBounds(id) => LedRouterError::NotFound(id),
-    })?;
+    let color = gate.check_led::<LedRouterError>(&canvas, session.as_ref(), color)?;
+    let led = canvas
+        .leds
+        .set(id, color, session.map(|session| session.id))
+        .await
+        .map_err(|err| match err {
+            LedRepoError::OutOfBounds(id) => LedRouterError::NotFound(id),
+            LedRepoError::Held(id) => LedRouterError::Held(id),
+        })?;
 
     Ok(Json(led))
 }
@@ -97,47 +246,148 @@ struct WsParams {
     colors_only: bool,
     #[serde_inline_default(100)]
     snapshot_interval: u64,
+    /// Opts into the text messages of [`PresenceMessage`] and sharing a
+    /// cursor with [`PresenceCommand`].
+    #[serde_inline_default(false)]
+    presence: bool,
+    /// Hardware clients identify themselves with these to show up in
+    /// `/api/devices`.
+    device_id: Option<String>,
+    firmware: Option<String>,
+    led_count: Option<usize>,
+    channel_order: Option<ChannelOrder>,
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn get_ws(
-    State(leds): State<LedRepo>,
+    canvas: Canvas,
+    State(devices): State<DeviceRepo>,
+    State(clients): State<ClientRepo>,
+    State(output): State<OutputRepo>,
+    State(sessions): State<SessionRepo>,
+    State(gate): State<WriteGate>,
+    State(voting): State<VotingRepo>,
     Query(WsParams {
         colors_only,
         snapshot_interval,
+        presence: presence_enabled,
+        device_id,
+        firmware,
+        led_count,
+        channel_order,
     }): Query<WsParams>,
     InsecureClientIp(ip): InsecureClientIp,
+    session: Option<Session>,
     ws: WebSocketUpgrade,
-) -> impl IntoResponse {
+) -> Result<impl IntoResponse, DeviceRouterError> {
+    if let Some(device_id) = &device_id {
+        device::check_identity(device_id, firmware.as_deref())?;
+    }
+
     let ws_client_id = Uuid::new_v4();
+    let painter = Painter {
+        canvas: canvas.clone(),
+        session: session.map(|session| session.id),
+        sessions,
+        gate,
+        voting,
+    };
+    let Canvas {
+        name,
+        leds,
+        frames,
+        presence,
+        ..
+    } = canvas;
     let snapshot_interval = snapshot_interval.max(100);
 
-    ws.on_upgrade(move |ws| {
+    if let Some(device_id) = &device_id {
+        if led_count.is_some_and(|count| count != leds.led_count()) {
+            warn!(
+                "Device {device_id} drives {} leds but canvas '{name}' has {}",
+                led_count.unwrap_or_default(),
+                leds.led_count()
+            );
+        }
+
+        let output_order = output.settings().await.channel_order;
+        if channel_order
+            .is_some_and(|order| order != ChannelOrder::Rgb && output_order != ChannelOrder::Rgb)
+        {
+            warn!("Device {device_id} reorders channels on top of the output pipeline");
+        }
+    }
+
+    let cursor = presence_enabled.then(|| presence.join(ws_client_id));
+    let client = clients.connect(ws_client_id, name.clone(), device_id.clone(), colors_only);
+    let device = device_id.map(|id| {
+        Arc::new(devices.connect(
+            DeviceInfo {
+                id,
+                firmware,
+                led_count,
+                channel_order,
+            },
+            name.clone(),
+            ip.to_string(),
+        ))
+    });
+
+    Ok(ws.on_upgrade(move |ws| {
         Box::pin(async move {
             let rx_span = info_span!(
                 "rx",
                 ws_client_id = ws_client_id.to_string(),
                 ip = ip.to_string(),
+                canvas = &*name,
             );
 
             let tx_span = info_span!(
                 "tx",
                 ws_client_id = ws_client_id.to_string(),
                 ip = ip.to_string(),
+                canvas = &*name,
                 colors_only = colors_only
             );
 
             let (tx, rx) = ws.split();
-            let tx = Arc::new(Mutex::new(tx));
-            let mut rx_task = spawn(rx_handler(rx, tx.clone(), leds.clone()).instrument(rx_span));
-            let mut tx_task =
-                spawn(tx_handler(tx, leds, colors_only, snapshot_interval).instrument(tx_span));
+            let (outbox, writer) = Outbox::new(tx, client, device.clone());
+            let mut writer_task = spawn(writer.instrument(tx_span.clone()));
+            let presence_task = presence_enabled.then(|| {
+                spawn(
+                    send_presence(outbox.clone(), clients, presence, name, ws_client_id)
+                        .instrument(tx_span.clone()),
+                )
+            });
+            let mut rx_task = spawn(
+                rx_handler(
+                    rx,
+                    outbox.clone(),
+                    leds.clone(),
+                    painter,
+                    device.clone(),
+                    cursor,
+                )
+                .instrument(rx_span),
+            );
+            let mut tx_task = spawn(
+                tx_handler(outbox, leds, frames, colors_only, snapshot_interval, device)
+                    .instrument(tx_span),
+            );
 
             tokio::select! {
-                _ = &mut rx_task => tx_task.abort(),
-                _ = &mut tx_task => rx_task.abort(),
+                _ = &mut rx_task => {}
+                _ = &mut tx_task => {}
+                _ = &mut writer_task => {}
             };
+            rx_task.abort();
+            tx_task.abort();
+            writer_task.abort();
+            if let Some(presence_task) = presence_task {
+                presence_task.abort();
+            }
         })
-    })
+    }))
 }
 
 impl From<Color> for [u8; 3] {
@@ -167,18 +417,47 @@ impl From<Led> for [u8; 11] {
 
 async fn rx_handler(
     mut rx: SplitStream<WebSocket>,
-    tx: Arc<Mutex<SplitSink<WebSocket, Message>>>,
+    outbox: Outbox,
     leds: LedRepo,
+    painter: Painter,
+    device: Option<Arc<DeviceHandle>>,
+    cursor: Option<CursorHandle>,
 ) {
+    let mut pending_cursor = None;
+    let mut next_cursor_move = Instant::now();
+
     loop {
-        match rx.next().await {
+        let message = tokio::select! {
+            message = rx.next() => message,
+            _ = sleep_until(next_cursor_move), if pending_cursor.is_some() => {
+                if let (Some(cursor), Some((led, color))) = (&cursor, pending_cursor.take()) {
+                    cursor.move_to(led, color);
+                    next_cursor_move = Instant::now() + CURSOR_INTERVAL;
+                }
+                continue;
+            }
+        };
+
+        match message {
             Some(Ok(message)) => {
-                let handle_message_result = handle_message(message, leds.clone()).await;
+                if let Some(device) = &device {
+                    device.seen();
+
+                    if let Some(round_trip) = ping_round_trip(&message) {
+                        device.record_latency(round_trip);
+                    }
+                }
+
+                let handle_message_result =
+                    handle_message(message, leds.clone(), &painter, cursor.is_some()).await;
 
                 // Purely for satiating react-use-websocket
                 if handle_message_result.send_pong {
-                    let mut tx = tx.lock().await;
-                    let _ = tx.send(Message::Text("pong".into())).await;
+                    outbox.send_control(Message::Text("pong".into()));
+                }
+
+                if let Some(cursor_move) = handle_message_result.cursor_move {
+                    pending_cursor = Some(cursor_move);
                 }
 
                 if handle_message_result.close_handler {
@@ -202,31 +481,84 @@ async fn rx_handler(
     info!("Connection closed");
 }
 
+/// Who writes through a websocket. Their session is looked up on every write
+/// since it may join a team after connecting.
+struct Painter {
+    canvas: Canvas,
+    session: Option<Uuid>,
+    sessions: SessionRepo,
+    gate: WriteGate,
+    voting: VotingRepo,
+}
+
+impl Painter {
+    /// Paints `color` on led `id`, or votes for it while the canvas is voted
+    /// on. Refused writes are dropped, as are votes without a session, like
+    /// `POST /voting/leds/{id}` refuses them.
+    async fn paint(&self, leds: &LedRepo, id: usize, color: Color) {
+        let session = self.session.and_then(|id| self.sessions.get(id));
+
+        if self.voting.check_write(&self.canvas.name).is_err() {
+            let Ok(color) = apply_palette(self.canvas.palette.as_deref(), color) else {
+                return;
+            };
+            if let Some(session) = session.filter(|_| id < leds.led_count()) {
+                self.voting.vote(session.id, id, color);
+            }
+            return;
+        }
+
+        if let Ok(color) = self
+            .gate
+            .check_led::<WriteError>(&self.canvas, session.as_ref(), color)
+        {
+            let _ = leds.set(id, color, self.session).await;
+        }
+    }
+}
+
 #[derive(Debug)]
 struct HandleMessageResult {
     close_handler: bool,
     send_pong: bool,
+    /// Where the client's cursor moved, `None` if it didn't.
+    cursor_move: Option<(Option<usize>, Option<Color>)>,
 }
 
-async fn handle_message(message: Message, leds: LedRepo) -> HandleMessageResult {
+async fn handle_message(
+    message: Message,
+    leds: LedRepo,
+    painter: &Painter,
+    presence: bool,
+) -> HandleMessageResult {
     let mut close_handler = false;
     let mut send_pong = false;
+    let mut cursor_move = None;
 
     match message {
-        Message::Binary(bytes) => {
-            if bytes.len() >= 5 {
-                let id = usize::from_be_bytes([0, 0, 0, 0, 0, 0, bytes[0], bytes[1]]);
-                let red = bytes[2];
-                let green = bytes[3];
-                let blue = bytes[4];
-
-                let _ = leds.set(id, Color { red, green, blue }).await;
-            }
+        Message::Binary(bytes) if bytes.len() >= 5 => {
+            let id = usize::from_be_bytes([0, 0, 0, 0, 0, 0, bytes[0], bytes[1]]);
+            let red = bytes[2];
+            let green = bytes[3];
+            let blue = bytes[4];
+
+            let color = Color { red, green, blue };
+
+            painter.paint(&leds, id, color).await;
         }
         Message::Text(utf8) => {
             let text = utf8.to_string();
             if text == "ping" {
                 send_pong = true;
+            } else if presence {
+                match serde_json::from_str(&text) {
+                    Ok(PresenceCommand::Cursor { led, color }) => {
+                        // Pointing outside the canvas hides the cursor.
+                        let led = led.filter(|led| *led < leds.led_count());
+                        cursor_move = Some((led, color));
+                    }
+                    Err(err) => warn!("Invalid presence message: {err}"),
+                }
             }
         }
         Message::Close(_) => {
@@ -238,47 +570,248 @@ async fn handle_message(message: Message, leds: LedRepo) -> HandleMessageResult
     HandleMessageResult {
         close_handler,
         send_pong,
+        cursor_move,
     }
 }
 
 async fn tx_handler(
-    tx: Arc<Mutex<SplitSink<WebSocket, Message>>>,
+    outbox: Outbox,
     leds: LedRepo,
+    frames: DeviceFrames,
     colors_only: bool,
     snapshot_interval: u64,
+    device: Option<Arc<DeviceHandle>>,
 ) {
+    if colors_only {
+        return send_device_frames(outbox, frames, snapshot_interval, device).await;
+    }
+
     let mut latest_generation = 0;
     loop {
         if latest_generation < leds.generation() {
-            let mut tx = tx.lock().await;
-            latest_generation = send_snapshot(&mut tx, &leds, colors_only).await.generation;
-            info!("Sent snapshot");
+            latest_generation = send_snapshot(&outbox, &leds).await.generation;
+            info!("Queued snapshot");
         }
 
         sleep(Duration::from_millis(snapshot_interval)).await;
     }
 }
 
+/// Devices get the frames of the canvas' websocket output, which went through
+/// the output pipeline, while browsers keep the logical colors so the web
+/// view stays true to what users picked. Identified devices also get their
+/// config, as a `{"config": {..}}` text message, on connect and on every
+/// edit.
+async fn send_device_frames(
+    outbox: Outbox,
+    frames: DeviceFrames,
+    snapshot_interval: u64,
+    device: Option<Arc<DeviceHandle>>,
+) {
+    let mut receiver = frames.subscribe();
+    receiver.mark_changed();
+    let mut config = match &device {
+        Some(device) => device.config(),
+        None => watch::channel(DeviceConfig::default()).1,
+    };
+    config.mark_changed();
+    let mut frame_interval = Duration::from_millis(snapshot_interval);
+    let mut ping = interval(DEVICE_PING_INTERVAL);
+
+    loop {
+        tokio::select! {
+            changed = receiver.changed() => {
+                // The sender lives as long as the canvas, so this only
+                // happens on shutdown.
+                if changed.is_err() {
+                    break;
+                }
+
+                let DeviceFrame { data, .. } = receiver.borrow_and_update().clone();
+                if data.is_empty() {
+                    continue;
+                }
+
+                outbox.send_frame(data);
+                info!("Queued frame");
+
+                sleep(frame_interval).await;
+            }
+            changed = config.changed(), if device.is_some() => {
+                // Configs are never removed from the repo, like above.
+                if changed.is_err() {
+                    break;
+                }
+
+                let current = *config.borrow_and_update();
+                frame_interval = Duration::from_millis(snapshot_interval).max(
+                    current
+                        .fps
+                        .map(|fps| Duration::from_secs(1) / fps)
+                        .unwrap_or_default(),
+                );
+                let heartbeat = current
+                    .heartbeat_seconds
+                    .map_or(DEVICE_PING_INTERVAL, Duration::from_secs);
+                // Configs saved before heartbeats were capped may still be
+                // too far out to schedule.
+                if heartbeat != ping.period() {
+                    match Instant::now().checked_add(heartbeat) {
+                        Some(start) => ping = interval_at(start, heartbeat),
+                        None => warn!("Ignoring heartbeat of {heartbeat:?}"),
+                    }
+                }
+
+                let message = json!({ "config": current }).to_string();
+                outbox.send_control(Message::Text(message.into()));
+                info!("Queued device config");
+            }
+            _ = ping.tick(), if device.is_some() => {
+                let sent_at = Utc::now().timestamp_millis().to_be_bytes();
+                outbox.send_control(Message::Ping(Bytes::copy_from_slice(&sent_at)));
+            }
+        }
+    }
+}
+
+/// Keeps presence clients up to date with the connection counts of their
+/// canvas and the cursors of the other clients.
+async fn send_presence(
+    outbox: Outbox,
+    clients: ClientRepo,
+    presence: PresenceRepo,
+    canvas: Arc<str>,
+    own_id: Uuid,
+) {
+    let (cursors, mut updates) = presence.subscribe();
+    let mut backlog: VecDeque<PresenceMessage> = cursors.into();
+    // Cursors this client was told about, to hide them after a lag.
+    let mut shown: BTreeSet<Uuid> = BTreeSet::new();
+    let mut ticker = interval(PRESENCE_INTERVAL);
+
+    loop {
+        let message = match backlog.pop_front() {
+            Some(message) => message,
+            None => tokio::select! {
+                _ = ticker.tick() => {
+                    let (browsers, devices) = clients.counts(&canvas);
+                    PresenceMessage::Presence { browsers, devices }
+                }
+                update = updates.recv() => match update {
+                    Ok(message) => message,
+                    // Missed hides would leave cursors behind, so start over
+                    // from where every cursor is now.
+                    Err(RecvError::Lagged(_)) => {
+                        let (cursors, resubscribed) = presence.subscribe();
+                        updates = resubscribed;
+                        backlog.extend(resync(&shown, cursors));
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+            },
+        };
+
+        if let PresenceMessage::Cursor { client, led, .. } = message {
+            if client == own_id {
+                continue;
+            }
+            if led.is_some() {
+                shown.insert(client);
+            } else {
+                shown.remove(&client);
+            }
+        }
+        outbox.send_presence(&message);
+    }
+}
+
+/// Hides the `shown` cursors missing from a fresh snapshot of `cursors`,
+/// followed by the snapshot itself.
+fn resync(shown: &BTreeSet<Uuid>, cursors: Vec<PresenceMessage>) -> Vec<PresenceMessage> {
+    let current: BTreeSet<Uuid> = cursors
+        .iter()
+        .filter_map(|cursor| match cursor {
+            PresenceMessage::Cursor { client, .. } => Some(*client),
+            _ => None,
+        })
+        .collect();
+
+    shown
+        .difference(&current)
+        .map(|client| PresenceMessage::Cursor {
+            client: *client,
+            led: None,
+            color: None,
+        })
+        .chain(cursors)
+        .collect()
+}
+
+/// Devices are pinged with the time the ping was sent, which they echo back.
+fn ping_round_trip(message: &Message) -> Option<Duration> {
+    let Message::Pong(payload) = message else {
+        return None;
+    };
+    let sent_at = i64::from_be_bytes(payload.as_ref().try_into().ok()?);
+
+    (Utc::now().timestamp_millis() - sent_at)
+        .try_into()
+        .ok()
+        .map(Duration::from_millis)
+}
+
 #[derive(Debug)]
 struct SendSnapshotResult {
     generation: usize,
 }
 
-async fn send_snapshot(
-    tx: &mut SplitSink<WebSocket, Message>,
-    leds: &LedRepo,
-    colors_only: bool,
-) -> SendSnapshotResult {
+async fn send_snapshot(outbox: &Outbox, leds: &LedRepo) -> SendSnapshotResult {
     let snapshot = leds.snapshot().await;
-    let leds = snapshot.leds.into_iter();
-    let bytes: Bytes = if colors_only {
-        leds.flat_map(|led| <[u8; 3]>::from(led.color)).collect()
-    } else {
-        leds.flat_map(<[u8; 11]>::from).collect()
-    };
-    let _ = tx.send(Message::Binary(bytes)).await;
+    let bytes: Bytes = snapshot
+        .leds
+        .into_iter()
+        .flat_map(<[u8; 11]>::from)
+        .collect();
+    outbox.send_frame(bytes);
 
     SendSnapshotResult {
         generation: snapshot.generation,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cursor(client: Uuid, led: Option<usize>) -> PresenceMessage {
+        PresenceMessage::Cursor {
+            client,
+            led,
+            color: None,
+        }
+    }
+
+    #[test]
+    fn resync_hides_cursors_gone_during_a_lag() {
+        let (stayed, left, joined) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let shown = BTreeSet::from([stayed, left]);
+
+        let messages = resync(
+            &shown,
+            vec![cursor(stayed, Some(3)), cursor(joined, Some(7))],
+        );
+
+        let messages: Vec<_> = messages
+            .iter()
+            .map(|message| match message {
+                PresenceMessage::Cursor { client, led, .. } => (*client, *led),
+                _ => panic!("unexpected message {message:?}"),
+            })
+            .collect();
+        assert_eq!(
+            messages,
+            [(left, None), (stayed, Some(3)), (joined, Some(7))]
+        );
+    }
+}
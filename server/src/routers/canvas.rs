@@ -0,0 +1,82 @@
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+    extract::{FromRef, FromRequestParts, OptionalFromRequestParts, Path, State},
+    http::{request::Parts, StatusCode},
+    routing::get,
+    Json, Router,
+};
+use axum_thiserror::ErrorStatus;
+use serde::Serialize;
+
+use crate::{
+    palette::Palette,
+    repo::canvas::{Canvas, CanvasRepo},
+    state::AppState,
+};
+
+pub fn get_router() -> Router<AppState> { Router::new().route("/canvases", get(get_canvases)) }
+
+#[derive(thiserror::Error, Debug, ErrorStatus)]
+pub enum CanvasRejection {
+    #[error("Canvas '{0}' does not exist")]
+    #[status(StatusCode::NOT_FOUND)]
+    NotFound(String),
+    #[error("Invalid path parameters")]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidPath,
+}
+
+/// Resolves the canvas named by the `{canvas}` path segment, or the default
+/// canvas for routes that are not nested under `/canvases/{canvas}`.
+impl<S> FromRequestParts<S> for Canvas
+where
+    S: Send + Sync,
+    CanvasRepo: FromRef<S>,
+{
+    type Rejection = CanvasRejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let canvases = CanvasRepo::from_ref(state);
+        let params =
+            <Path<HashMap<String, String>> as OptionalFromRequestParts<S>>::from_request_parts(
+                parts, state,
+            )
+            .await
+            .map_err(|_| CanvasRejection::InvalidPath)?;
+
+        match params.and_then(|Path(mut params)| params.remove("canvas")) {
+            Some(name) => canvases.get(&name).ok_or(CanvasRejection::NotFound(name)),
+            None => Ok(canvases.default_canvas()),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct CanvasResponse {
+    name: String,
+    led_count: usize,
+    default: bool,
+    palette: Option<Arc<Palette>>,
+}
+
+async fn get_canvases(State(canvases): State<CanvasRepo>) -> Json<Vec<CanvasResponse>> {
+    let default = canvases.default_canvas();
+
+    Json(
+        canvases
+            .iter()
+            .map(|canvas| CanvasResponse {
+                name: canvas.name.to_string(),
+                led_count: canvas.leds.led_count(),
+                default: canvas.name == default.name,
+                palette: canvas.palette.clone(),
+            })
+            .collect(),
+    )
+}
+
+/// The colors the canvas can be painted with, `null` when any goes.
+pub async fn get_palette(Canvas { palette, .. }: Canvas) -> Json<Option<Arc<Palette>>> {
+    Json(palette)
+}
@@ -0,0 +1,12 @@
+use axum::{extract::State, routing::get, Json, Router};
+
+use crate::{
+    repo::client::{Client, ClientRepo},
+    state::AppState,
+};
+
+pub fn get_router() -> Router<AppState> { Router::new().route("/clients", get(get_clients)) }
+
+async fn get_clients(State(clients): State<ClientRepo>) -> Json<Vec<Client>> {
+    Json(clients.list())
+}
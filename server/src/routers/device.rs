@@ -0,0 +1,92 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+use axum_thiserror::ErrorStatus;
+
+use crate::{
+    repo::device::{Device, DeviceConfig, DeviceRepo},
+    state::AppState,
+};
+
+const MAX_ID_LENGTH: usize = 64;
+const MAX_FIRMWARE_LENGTH: usize = 64;
+const MAX_FPS: u32 = 120;
+/// Devices count the heartbeat in milliseconds, often in 32 bits.
+const MAX_HEARTBEAT_SECONDS: u64 = 60 * 60;
+
+pub fn get_router() -> Router<AppState> {
+    Router::new()
+        .route("/devices", get(get_devices))
+        .route("/devices/{id}/config", get(get_config).put(put_config))
+}
+
+#[derive(thiserror::Error, Debug, ErrorStatus)]
+pub enum DeviceRouterError {
+    #[error("{0} must be greater than 0")]
+    #[status(StatusCode::UNPROCESSABLE_ENTITY)]
+    Zero(&'static str),
+    #[error("{0} must be between 1 and {1}")]
+    #[status(StatusCode::UNPROCESSABLE_ENTITY)]
+    OutOfRange(&'static str, u64),
+    #[error("{0} must be at most {1} characters")]
+    #[status(StatusCode::UNPROCESSABLE_ENTITY)]
+    TooLong(&'static str, usize),
+    #[error("Failed to save device config: {0}")]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Persist(#[from] std::io::Error),
+}
+
+/// Devices are kept around after disconnecting, so what they claim to be is
+/// bounded.
+pub fn check_identity(id: &str, firmware: Option<&str>) -> Result<(), DeviceRouterError> {
+    if id.chars().count() > MAX_ID_LENGTH {
+        return Err(DeviceRouterError::TooLong("device_id", MAX_ID_LENGTH));
+    }
+    if firmware.is_some_and(|firmware| firmware.chars().count() > MAX_FIRMWARE_LENGTH) {
+        return Err(DeviceRouterError::TooLong("firmware", MAX_FIRMWARE_LENGTH));
+    }
+
+    Ok(())
+}
+
+async fn get_devices(State(devices): State<DeviceRepo>) -> Json<Vec<Device>> {
+    Json(devices.list())
+}
+
+async fn get_config(
+    State(devices): State<DeviceRepo>,
+    Path(id): Path<String>,
+) -> Json<DeviceConfig> {
+    Json(devices.config(&id))
+}
+
+/// Devices don't have to be connected, or even known yet, to be configured.
+async fn put_config(
+    State(devices): State<DeviceRepo>,
+    Path(id): Path<String>,
+    Json(config): Json<DeviceConfig>,
+) -> Result<Json<DeviceConfig>, DeviceRouterError> {
+    check_identity(&id, None)?;
+    if config.led_count == Some(0) {
+        return Err(DeviceRouterError::Zero("led_count"));
+    }
+    if config.fps.is_some_and(|fps| !(1..=MAX_FPS).contains(&fps)) {
+        return Err(DeviceRouterError::OutOfRange("fps", MAX_FPS.into()));
+    }
+    if config
+        .heartbeat_seconds
+        .is_some_and(|seconds| !(1..=MAX_HEARTBEAT_SECONDS).contains(&seconds))
+    {
+        return Err(DeviceRouterError::OutOfRange(
+            "heartbeat_seconds",
+            MAX_HEARTBEAT_SECONDS,
+        ));
+    }
+
+    devices.set_config(&id, config).await?;
+
+    Ok(Json(config))
+}
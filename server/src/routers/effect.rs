@@ -0,0 +1,82 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use axum_thiserror::ErrorStatus;
+use serde::Serialize;
+
+use crate::{
+    effects::{scroll_text, ScrollText},
+    gate::WriteGate,
+    palette::PaletteError,
+    repo::{canvas::Canvas, team::TeamError, voting::VotingError},
+    state::AppState,
+};
+
+const MAX_MESSAGE_LENGTH: usize = 256;
+
+pub fn get_router() -> Router<AppState> {
+    Router::new()
+        .route("/effects", get(get_effect).delete(delete_effect))
+        .route("/effects/text", post(post_text_effect))
+}
+
+#[derive(thiserror::Error, Debug, ErrorStatus)]
+pub enum EffectRouterError {
+    #[error("Message must be between 1 and {MAX_MESSAGE_LENGTH} characters")]
+    #[status(StatusCode::UNPROCESSABLE_ENTITY)]
+    InvalidMessage,
+    #[error(transparent)]
+    #[status(StatusCode::FORBIDDEN)]
+    Team(#[from] TeamError),
+    #[error(transparent)]
+    #[status(StatusCode::FORBIDDEN)]
+    Voting(#[from] VotingError),
+    #[error(transparent)]
+    #[status(StatusCode::UNPROCESSABLE_ENTITY)]
+    Palette(#[from] PaletteError),
+}
+
+#[derive(Serialize)]
+struct EffectResponse {
+    effect: Option<&'static str>,
+}
+
+async fn get_effect(Canvas { effects, .. }: Canvas) -> Json<EffectResponse> {
+    Json(EffectResponse {
+        effect: effects.current().await,
+    })
+}
+
+async fn delete_effect(Canvas { effects, .. }: Canvas) -> Json<EffectResponse> {
+    Json(EffectResponse {
+        effect: effects.stop().await,
+    })
+}
+
+async fn post_text_effect(
+    canvas: Canvas,
+    State(gate): State<WriteGate>,
+    Json(mut options): Json<ScrollText>,
+) -> Result<(StatusCode, Json<ScrollText>), EffectRouterError> {
+    let length = options.message.chars().count();
+    if length == 0 || length > MAX_MESSAGE_LENGTH {
+        return Err(EffectRouterError::InvalidMessage);
+    }
+    gate.check_bulk::<EffectRouterError>(&canvas, [&mut options.color, &mut options.background])?;
+
+    let Canvas {
+        leds,
+        layout,
+        effects,
+        ..
+    } = canvas;
+
+    effects
+        .start("text", scroll_text(leds, layout, options.clone()))
+        .await;
+
+    Ok((StatusCode::ACCEPTED, Json(options)))
+}
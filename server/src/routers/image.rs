@@ -0,0 +1,100 @@
+use axum::{
+    body::Bytes,
+    extract::{DefaultBodyLimit, Query, State},
+    http::StatusCode,
+    routing::post,
+    Json, Router,
+};
+use axum_thiserror::ErrorStatus;
+use serde::Deserialize;
+use serde_inline_default::serde_inline_default;
+
+use crate::{
+    gate::WriteGate,
+    imaging::{map_image, Fit, ImageOptions},
+    palette::PaletteError,
+    repo::{canvas::Canvas, team::TeamError, voting::VotingError},
+    state::AppState,
+    types::Color,
+};
+
+const MAX_IMAGE_BYTES: usize = 16 * 1024 * 1024;
+
+pub fn get_router() -> Router<AppState> {
+    Router::new().route(
+        "/image",
+        post(post_image).layer(DefaultBodyLimit::max(MAX_IMAGE_BYTES)),
+    )
+}
+
+#[derive(thiserror::Error, Debug, ErrorStatus)]
+pub enum ImageRouterError {
+    #[error("Could not read image: {0}")]
+    #[status(StatusCode::UNPROCESSABLE_ENTITY)]
+    Decode(#[from] image::ImageError),
+    #[error(transparent)]
+    #[status(StatusCode::FORBIDDEN)]
+    Team(#[from] TeamError),
+    #[error(transparent)]
+    #[status(StatusCode::FORBIDDEN)]
+    Voting(#[from] VotingError),
+    #[error(transparent)]
+    #[status(StatusCode::UNPROCESSABLE_ENTITY)]
+    Palette(#[from] PaletteError),
+}
+
+#[serde_inline_default]
+#[derive(Deserialize)]
+struct ImageParams {
+    #[serde(default)]
+    fit: Fit,
+    #[serde_inline_default(256)]
+    levels: u16,
+    #[serde_inline_default(false)]
+    dither: bool,
+    /// Save the result as this scene instead of applying it.
+    #[serde(default)]
+    scene: Option<String>,
+}
+
+/// Maps a PNG/JPEG request body onto the canvas, applying it atomically or
+/// saving it as a scene. Colors are snapped to the canvas' palette, if any,
+/// since images hardly ever stick to one.
+async fn post_image(
+    canvas: Canvas,
+    State(gate): State<WriteGate>,
+    Query(ImageParams {
+        fit,
+        levels,
+        dither,
+        scene,
+    }): Query<ImageParams>,
+    body: Bytes,
+) -> Result<Json<Vec<Color>>, ImageRouterError> {
+    let options = ImageOptions {
+        fit,
+        levels,
+        dither,
+    };
+    let mut colors = tokio::task::block_in_place(|| {
+        map_image(&body, &canvas.layout, canvas.leds.led_count(), options)
+    })?;
+    if let Some(palette) = &canvas.palette {
+        for color in &mut colors {
+            *color = palette.snap(*color);
+        }
+    }
+
+    match scene {
+        Some(scene) => canvas.scenes.save(scene, colors.clone()).await,
+        None => {
+            gate.check_bulk::<ImageRouterError>(&canvas, colors.iter_mut())?;
+            let _ = canvas
+                .leds
+                .set_many(colors.iter().copied().enumerate())
+                .await;
+        }
+    }
+
+    Ok(Json(colors))
+}
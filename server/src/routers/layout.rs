@@ -0,0 +1,149 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{get, post},
+    Form, Json, Router,
+};
+use axum_thiserror::ErrorStatus;
+use serde::Deserialize;
+
+use crate::{
+    gate::WriteGate,
+    layout::Cell,
+    palette::PaletteError,
+    repo::{
+        canvas::Canvas,
+        led::{Led, LedRepoError},
+        session::Session,
+        team::TeamError,
+        voting::VotingError,
+    },
+    state::AppState,
+    types::Color,
+};
+
+pub fn get_router() -> Router<AppState> {
+    Router::new()
+        .route("/layout", get(get_layout))
+        .route("/pixels/{x}/{y}", get(get_pixel).post(post_pixel))
+        .route("/draw/rect", post(post_rect))
+        .route("/draw/line", post(post_line))
+}
+
+#[derive(thiserror::Error, Debug, ErrorStatus)]
+pub enum LayoutRouterError {
+    #[error("There is no led at ({0}, {1})")]
+    #[status(StatusCode::NOT_FOUND)]
+    NoLed(i32, i32),
+    #[error("The led at ({0}, {1}) is currently controlled by another source")]
+    #[status(StatusCode::LOCKED)]
+    Held(i32, i32),
+    #[error(transparent)]
+    #[status(StatusCode::FORBIDDEN)]
+    Team(#[from] TeamError),
+    #[error(transparent)]
+    #[status(StatusCode::FORBIDDEN)]
+    Voting(#[from] VotingError),
+    #[error(transparent)]
+    #[status(StatusCode::UNPROCESSABLE_ENTITY)]
+    Palette(#[from] PaletteError),
+}
+
+async fn get_layout(Canvas { layout, .. }: Canvas) -> impl IntoResponse {
+    Json(layout.describe()).into_response()
+}
+
+async fn get_pixel(
+    Canvas { leds, layout, .. }: Canvas,
+    Path(Cell { x, y }): Path<Cell>,
+) -> Result<Json<Led>, LayoutRouterError> {
+    let id = layout.id_at(x, y).ok_or(LayoutRouterError::NoLed(x, y))?;
+    let led = leds.get(id).await.ok_or(LayoutRouterError::NoLed(x, y))?;
+
+    Ok(Json(led))
+}
+
+async fn post_pixel(
+    canvas: Canvas,
+    State(gate): State<WriteGate>,
+    Path(Cell { x, y }): Path<Cell>,
+    session: Option<Session>,
+    Form(color): Form<Color>,
+) -> Result<Json<Led>, LayoutRouterError> {
+    let id = canvas
+        .layout
+        .id_at(x, y)
+        .ok_or(LayoutRouterError::NoLed(x, y))?;
+    let color = gate.check_led::<LayoutRouterError>(&canvas, session.as_ref(), color)?;
+    let led = canvas
+        .leds
+        .set(id, color, session.map(|session| session.id))
+        .await
+        .map_err(|err| match err {
+            LedRepoError::OutOfBounds(_) => LayoutRouterError::NoLed(x, y),
+            LedRepoError::Held(_) => LayoutRouterError::Held(x, y),
+        })?;
+
+    Ok(Json(led))
+}
+
+#[derive(Deserialize)]
+struct Rect {
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+    color: Color,
+}
+
+/// Fills every led inside the rectangle, cells without a led are skipped.
+async fn post_rect(
+    canvas: Canvas,
+    State(gate): State<WriteGate>,
+    Json(Rect {
+        x,
+        y,
+        width,
+        height,
+        mut color,
+    }): Json<Rect>,
+) -> Result<StatusCode, LayoutRouterError> {
+    gate.check_bulk::<LayoutRouterError>(&canvas, [&mut color])?;
+
+    let ids = canvas.layout.rect(x, y, width, height);
+    // Ids come from the canvas' own layout, so they are always in bounds.
+    let _ = canvas
+        .leds
+        .set_many(ids.into_iter().map(|id| (id, color)))
+        .await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+struct Line {
+    from: Cell,
+    to: Cell,
+    color: Color,
+}
+
+async fn post_line(
+    canvas: Canvas,
+    State(gate): State<WriteGate>,
+    Json(Line {
+        from,
+        to,
+        mut color,
+    }): Json<Line>,
+) -> Result<StatusCode, LayoutRouterError> {
+    gate.check_bulk::<LayoutRouterError>(&canvas, [&mut color])?;
+
+    let ids = canvas.layout.line((from.x, from.y), (to.x, to.y));
+    let _ = canvas
+        .leds
+        .set_many(ids.into_iter().map(|id| (id, color)))
+        .await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
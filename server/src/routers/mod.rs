@@ -1 +1,17 @@
 pub mod api;
+pub mod canvas;
+pub mod client;
+pub mod device;
+pub mod effect;
+pub mod image;
+pub mod layout;
+pub mod outbox;
+pub mod output;
+pub mod scene;
+pub mod schedule;
+pub mod session;
+pub mod sse;
+pub mod team;
+pub mod voting;
+pub mod webhook;
+pub mod wled;
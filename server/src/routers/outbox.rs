@@ -0,0 +1,227 @@
+//! Outbound side of `/leds/ws` connections. Frames are latest-wins, so a
+//! client that can't keep up skips to the newest one instead of falling
+//! further and further behind, while control messages (pongs, pings and
+//! configs) are queued ahead of them. Presence messages go in between and
+//! replace the queued one of the same kind, so a burst of cursor moves only
+//! sends where each cursor ended up.
+
+use std::{
+    collections::VecDeque,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use axum::{
+    body::Bytes,
+    extract::ws::{Message, WebSocket},
+};
+use futures::{stream::SplitSink, SinkExt};
+use tokio::{
+    sync::{
+        mpsc::{self, error::TrySendError},
+        Notify,
+    },
+    time::{timeout, Instant},
+};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::repo::{client::ClientHandle, device::DeviceHandle, presence::PresenceMessage};
+
+const CONTROL_QUEUE_SIZE: usize = 16;
+/// A client stuck on a single send for this long is disconnected.
+const SEND_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Clone)]
+pub(super) struct Outbox {
+    control: mpsc::Sender<Message>,
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    presence: Mutex<VecDeque<(PresenceSlot, Message)>>,
+    presence_ready: Notify,
+    frame: Mutex<Option<QueuedFrame>>,
+    frame_ready: Notify,
+    client: ClientHandle,
+    device: Option<Arc<DeviceHandle>>,
+}
+
+/// Presence messages superseding each other, cursors are per client.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PresenceSlot {
+    Presence,
+    Cursor(Uuid),
+    Scores,
+    Round,
+    RoundResults,
+}
+
+impl From<&PresenceMessage> for PresenceSlot {
+    fn from(message: &PresenceMessage) -> Self {
+        match message {
+            PresenceMessage::Presence { .. } => Self::Presence,
+            PresenceMessage::Cursor { client, .. } => Self::Cursor(*client),
+            PresenceMessage::Scores { .. } => Self::Scores,
+            PresenceMessage::Round(_) => Self::Round,
+            PresenceMessage::RoundResults(_) => Self::RoundResults,
+        }
+    }
+}
+
+struct QueuedFrame {
+    data: Bytes,
+    queued_at: Instant,
+}
+
+impl Outbox {
+    /// Creates the outbox of a connection along with the writer draining it
+    /// into `sink`, which finishes once the client is gone.
+    pub(super) fn new(
+        sink: SplitSink<WebSocket, Message>,
+        client: ClientHandle,
+        device: Option<Arc<DeviceHandle>>,
+    ) -> (Self, impl Future<Output = ()>) {
+        let (control, control_rx) = mpsc::channel(CONTROL_QUEUE_SIZE);
+        let shared = Arc::new(Shared {
+            presence: Mutex::new(VecDeque::new()),
+            presence_ready: Notify::new(),
+            frame: Mutex::new(None),
+            frame_ready: Notify::new(),
+            client,
+            device,
+        });
+
+        (
+            Self {
+                control,
+                shared: shared.clone(),
+            },
+            write(sink, control_rx, shared),
+        )
+    }
+
+    /// Queues a frame, replacing the one still waiting to be sent, if any.
+    pub(super) fn send_frame(&self, data: Bytes) {
+        let superseded = self
+            .shared
+            .frame
+            .lock()
+            .expect("outbox lock poisoned")
+            .replace(QueuedFrame {
+                data,
+                queued_at: Instant::now(),
+            })
+            .is_some();
+
+        if superseded {
+            self.shared.client.record_dropped();
+            if let Some(device) = &self.shared.device {
+                device.record_dropped();
+            }
+        }
+
+        self.shared.frame_ready.notify_one();
+    }
+
+    /// Queues a presence message, replacing the one of the same kind still
+    /// waiting to be sent, if any.
+    pub(super) fn send_presence(&self, message: &PresenceMessage) {
+        let Ok(json) = serde_json::to_string(message) else {
+            return;
+        };
+        let slot = PresenceSlot::from(message);
+        let message = Message::Text(json.into());
+
+        let mut presence = self.shared.presence.lock().expect("outbox lock poisoned");
+        match presence.iter_mut().find(|(other, _)| *other == slot) {
+            Some((_, queued)) => *queued = message,
+            None => presence.push_back((slot, message)),
+        }
+        drop(presence);
+
+        self.shared.presence_ready.notify_one();
+    }
+
+    /// Queues a message to be sent before any presence message or frame. Only fails once the
+    /// queue is full, which means the writer is stuck on a send and about to
+    /// time out anyway.
+    pub(super) fn send_control(&self, message: Message) {
+        match self.control.try_send(message) {
+            Ok(()) | Err(TrySendError::Closed(_)) => {}
+            Err(TrySendError::Full(_)) => warn!("Control queue full, dropping message"),
+        }
+    }
+}
+
+async fn write(
+    mut sink: SplitSink<WebSocket, Message>,
+    mut control: mpsc::Receiver<Message>,
+    shared: Arc<Shared>,
+) {
+    let mut behind = false;
+
+    loop {
+        let (message, queued_at) = tokio::select! {
+            biased;
+            message = control.recv() => match message {
+                Some(message) => (message, None),
+                None => break,
+            },
+            _ = shared.presence_ready.notified() => {
+                let mut presence = shared.presence.lock().expect("outbox lock poisoned");
+                let Some((_, message)) = presence.pop_front() else {
+                    continue;
+                };
+                if !presence.is_empty() {
+                    shared.presence_ready.notify_one();
+                }
+                (message, None)
+            }
+            _ = shared.frame_ready.notified() => {
+                let frame = shared.frame.lock().expect("outbox lock poisoned").take();
+                match frame {
+                    Some(QueuedFrame { data, queued_at }) => {
+                        (Message::Binary(data), Some(queued_at))
+                    }
+                    None => continue,
+                }
+            }
+        };
+
+        let started = Instant::now();
+        if let Some(queued_at) = queued_at {
+            shared.client.record_sending(queued_at);
+        }
+        match timeout(SEND_TIMEOUT, sink.send(message)).await {
+            Ok(Ok(())) => {}
+            Ok(Err(err)) => {
+                info!("Failed to send message: {err}. Closing connection...");
+                break;
+            }
+            Err(_) => {
+                warn!("Client stalled for {SEND_TIMEOUT:?}. Closing connection...");
+                break;
+            }
+        }
+
+        let Some(queued_at) = queued_at else {
+            continue;
+        };
+
+        let lag = queued_at.elapsed();
+        if shared.client.record_frame(lag) != behind {
+            behind = !behind;
+            if behind {
+                warn!("Client can't keep up, frames take {lag:?} to go out");
+            } else {
+                info!("Client caught up");
+            }
+        }
+
+        if let Some(device) = &shared.device {
+            device.record_frame(started.elapsed());
+        }
+    }
+}
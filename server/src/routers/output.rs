@@ -0,0 +1,46 @@
+use axum::{extract::State, routing::get, Json, Router};
+
+use crate::{
+    pipeline::{OutputSettings, PowerEstimate},
+    repo::{
+        canvas::Canvas,
+        output::{OutputRepo, SinkStatus},
+    },
+    state::AppState,
+};
+
+pub fn get_router() -> Router<AppState> {
+    Router::new()
+        .route("/output", get(get_output).put(put_output))
+        .route("/outputs", get(get_outputs))
+}
+
+async fn get_output(State(output): State<OutputRepo>) -> Json<OutputSettings> {
+    Json(output.settings().await)
+}
+
+async fn put_output(
+    State(output): State<OutputRepo>,
+    Json(settings): Json<OutputSettings>,
+) -> Json<OutputSettings> {
+    output.set_settings(settings).await;
+
+    Json(settings)
+}
+
+async fn get_outputs(State(output): State<OutputRepo>) -> Json<Vec<SinkStatus>> {
+    Json(output.sinks().await)
+}
+
+pub async fn get_output_power(
+    Canvas { name, leds, .. }: Canvas,
+    State(output): State<OutputRepo>,
+) -> Json<PowerEstimate> {
+    let snapshot = leds.snapshot().await;
+    let frame = output
+        .canvas_pipeline(&name)
+        .await
+        .frame(snapshot.leds.into_iter().map(|led| led.color));
+
+    Json(frame.power)
+}
@@ -0,0 +1,135 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use axum_thiserror::ErrorStatus;
+use serde::Deserialize;
+
+use crate::{
+    events::{AppEvent, EventBus},
+    gate::WriteGate,
+    palette::PaletteError,
+    repo::{canvas::Canvas, team::TeamError, voting::VotingError},
+    state::AppState,
+    types::Color,
+};
+
+pub fn get_router() -> Router<AppState> {
+    Router::new()
+        .route("/scenes", get(get_scenes))
+        .route(
+            "/scenes/{scene}",
+            get(get_scene).put(put_scene).delete(delete_scene),
+        )
+        .route("/scenes/{scene}/apply", post(post_apply_scene))
+}
+
+#[derive(thiserror::Error, Debug, ErrorStatus)]
+pub enum SceneRouterError {
+    #[error("Scene '{0}' does not exist")]
+    #[status(StatusCode::NOT_FOUND)]
+    NotFound(String),
+    #[error(transparent)]
+    #[status(StatusCode::FORBIDDEN)]
+    Team(#[from] TeamError),
+    #[error(transparent)]
+    #[status(StatusCode::FORBIDDEN)]
+    Voting(#[from] VotingError),
+    #[error(transparent)]
+    #[status(StatusCode::UNPROCESSABLE_ENTITY)]
+    Palette(#[from] PaletteError),
+}
+
+#[derive(Deserialize)]
+struct ScenePath {
+    scene: String,
+}
+
+async fn get_scenes(Canvas { scenes, .. }: Canvas) -> Json<Vec<String>> {
+    Json(scenes.names().await)
+}
+
+async fn get_scene(
+    Canvas { scenes, .. }: Canvas,
+    Path(ScenePath { scene }): Path<ScenePath>,
+) -> Result<Json<Vec<Color>>, SceneRouterError> {
+    let colors = scenes
+        .get(&scene)
+        .await
+        .ok_or(SceneRouterError::NotFound(scene))?;
+
+    Ok(Json(colors))
+}
+
+/// Saves the current colors of the canvas as a scene.
+async fn put_scene(
+    Canvas { leds, scenes, .. }: Canvas,
+    Path(ScenePath { scene }): Path<ScenePath>,
+) -> Json<Vec<Color>> {
+    let colors: Vec<Color> = leds
+        .snapshot()
+        .await
+        .leds
+        .into_iter()
+        .map(|led| led.color)
+        .collect();
+    scenes.save(scene, colors.clone()).await;
+
+    Json(colors)
+}
+
+async fn delete_scene(
+    Canvas { scenes, .. }: Canvas,
+    Path(ScenePath { scene }): Path<ScenePath>,
+) -> Result<StatusCode, SceneRouterError> {
+    scenes
+        .remove(&scene)
+        .await
+        .ok_or(SceneRouterError::NotFound(scene))?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn post_apply_scene(
+    canvas: Canvas,
+    State(events): State<EventBus>,
+    State(gate): State<WriteGate>,
+    Path(ScenePath { scene }): Path<ScenePath>,
+) -> Result<StatusCode, SceneRouterError> {
+    apply_scene(&canvas, &scene, &events, &gate).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn apply_scene(
+    canvas: &Canvas,
+    scene: &str,
+    events: &EventBus,
+    gate: &WriteGate,
+) -> Result<(), SceneRouterError> {
+    let mut colors = canvas
+        .scenes
+        .get(scene)
+        .await
+        .ok_or_else(|| SceneRouterError::NotFound(scene.to_string()))?;
+
+    // Scenes are applied as saved or not at all, snapping would change them.
+    if let Some(palette) = &canvas.palette {
+        if let Some(color) = colors.iter().find(|color| !palette.contains(**color)) {
+            return Err(PaletteError::OffPalette(*color).into());
+        }
+    }
+    gate.check_bulk::<SceneRouterError>(canvas, colors.iter_mut())?;
+
+    // Scenes are saved from the canvas they belong to, so they always fit.
+    let _ = canvas.leds.set_many(colors.into_iter().enumerate()).await;
+
+    events.publish(AppEvent::SceneApplied {
+        canvas: canvas.name.clone(),
+        scene: scene.to_string(),
+    });
+
+    Ok(())
+}
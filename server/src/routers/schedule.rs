@@ -0,0 +1,127 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{delete, get},
+    Json, Router,
+};
+use axum_thiserror::ErrorStatus;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    repo::{
+        canvas::CanvasRepo,
+        schedule::{Schedule, ScheduleRepo, Trigger},
+    },
+    solar::{Coordinates, SolarTimes},
+    state::AppState,
+};
+
+/// Solar offsets beyond a day would skip the event they're relative to.
+const MAX_OFFSET_MINUTES: i64 = 24 * 60;
+
+pub fn get_router() -> Router<AppState> {
+    Router::new()
+        .route("/schedules", get(get_schedules).post(post_schedule))
+        .route("/schedules/{id}", delete(delete_schedule))
+        .route("/solar", get(get_solar))
+}
+
+#[derive(thiserror::Error, Debug, ErrorStatus)]
+pub enum ScheduleRouterError {
+    #[error("Schedule with id {0} does not exist")]
+    #[status(StatusCode::NOT_FOUND)]
+    NotFound(Uuid),
+    #[error("Solar triggers require LATITUDE and LONGITUDE to be configured")]
+    #[status(StatusCode::UNPROCESSABLE_ENTITY)]
+    MissingCoordinates,
+    #[error("Canvas '{0}' does not exist")]
+    #[status(StatusCode::UNPROCESSABLE_ENTITY)]
+    UnknownCanvas(String),
+    #[error("Solar offsets must be within {MAX_OFFSET_MINUTES} minutes of the event")]
+    #[status(StatusCode::UNPROCESSABLE_ENTITY)]
+    InvalidOffset,
+}
+
+#[derive(Serialize)]
+struct ScheduleResponse {
+    id: Uuid,
+    #[serde(flatten)]
+    schedule: Schedule,
+    next_run: Option<DateTime<Utc>>,
+}
+
+impl ScheduleResponse {
+    fn new(id: Uuid, schedule: Schedule, coordinates: Option<Coordinates>) -> Self {
+        Self {
+            id,
+            next_run: schedule.trigger.next_after(Utc::now(), coordinates),
+            schedule,
+        }
+    }
+}
+
+async fn get_schedules(
+    State(schedules): State<ScheduleRepo>,
+    State(coordinates): State<Option<Coordinates>>,
+) -> Json<Vec<ScheduleResponse>> {
+    Json(
+        schedules
+            .list()
+            .await
+            .into_iter()
+            .map(|(id, schedule)| ScheduleResponse::new(id, schedule, coordinates))
+            .collect(),
+    )
+}
+
+async fn post_schedule(
+    State(schedules): State<ScheduleRepo>,
+    State(canvases): State<CanvasRepo>,
+    State(coordinates): State<Option<Coordinates>>,
+    Json(schedule): Json<Schedule>,
+) -> Result<(StatusCode, Json<ScheduleResponse>), ScheduleRouterError> {
+    if schedule.trigger.requires_coordinates() && coordinates.is_none() {
+        return Err(ScheduleRouterError::MissingCoordinates);
+    }
+
+    if let Trigger::Solar { offset_minutes, .. } = schedule.trigger {
+        if !(-MAX_OFFSET_MINUTES..=MAX_OFFSET_MINUTES).contains(&offset_minutes) {
+            return Err(ScheduleRouterError::InvalidOffset);
+        }
+    }
+
+    if let Some(canvas) = &schedule.canvas {
+        canvases
+            .get(canvas)
+            .ok_or_else(|| ScheduleRouterError::UnknownCanvas(canvas.clone()))?;
+    }
+
+    let id = schedules.insert(schedule.clone()).await;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(ScheduleResponse::new(id, schedule, coordinates)),
+    ))
+}
+
+async fn delete_schedule(
+    State(schedules): State<ScheduleRepo>,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, ScheduleRouterError> {
+    schedules
+        .remove(id)
+        .await
+        .ok_or(ScheduleRouterError::NotFound(id))?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn get_solar(
+    State(coordinates): State<Option<Coordinates>>,
+) -> Result<Json<SolarTimes>, ScheduleRouterError> {
+    let coordinates = coordinates.ok_or(ScheduleRouterError::MissingCoordinates)?;
+
+    Ok(Json(SolarTimes::on(Utc::now().date_naive(), coordinates)))
+}
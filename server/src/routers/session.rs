@@ -0,0 +1,187 @@
+use axum::{
+    extract::{FromRef, FromRequestParts, OptionalFromRequestParts, Path, Query, State},
+    http::{
+        header::{AUTHORIZATION, COOKIE, SET_COOKIE},
+        request::Parts,
+        StatusCode,
+    },
+    response::IntoResponse,
+    routing::{get, post},
+    Json, Router,
+};
+use axum_client_ip::InsecureClientIp;
+use axum_thiserror::ErrorStatus;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    repo::session::{Session, SessionRepo, TooManySessions, SESSION_LIFETIME},
+    state::AppState,
+};
+
+const COOKIE_NAME: &str = "session";
+const MAX_NICKNAME_LENGTH: usize = 32;
+
+pub fn get_router() -> Router<AppState> {
+    Router::new()
+        .route("/sessions", post(post_session))
+        .route("/sessions/me", get(get_own_session).put(put_own_session))
+        .route("/sessions/{id}", get(get_session))
+}
+
+#[derive(thiserror::Error, Debug, ErrorStatus)]
+pub enum SessionRouterError {
+    #[error("No valid session, create one with POST /sessions")]
+    #[status(StatusCode::UNAUTHORIZED)]
+    Missing,
+    #[error("Session with id {0} does not exist")]
+    #[status(StatusCode::NOT_FOUND)]
+    NotFound(Uuid),
+    #[error("Nickname must be at most {MAX_NICKNAME_LENGTH} printable characters")]
+    #[status(StatusCode::UNPROCESSABLE_ENTITY)]
+    InvalidNickname,
+    #[error(transparent)]
+    #[status(StatusCode::TOO_MANY_REQUESTS)]
+    TooManySessions(#[from] TooManySessions),
+}
+
+#[derive(Deserialize)]
+struct TokenQuery {
+    session: Option<String>,
+}
+
+/// The token of the request's session, from an `Authorization: Bearer`
+/// header, the session cookie or, since browsers can't set headers on
+/// websockets, a `session` query parameter.
+fn session_token(parts: &Parts) -> Option<String> {
+    let bearer = parts
+        .headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if let Some(token) = bearer {
+        return Some(token.to_string());
+    }
+
+    let cookie = parts
+        .headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .find(|(name, _)| *name == COOKIE_NAME);
+    if let Some((_, token)) = cookie {
+        return Some(token.to_string());
+    }
+
+    Query::<TokenQuery>::try_from_uri(&parts.uri)
+        .ok()?
+        .0
+        .session
+}
+
+/// The session of the request, if it carries a valid token.
+impl<S> OptionalFromRequestParts<S> for Session
+where
+    S: Send + Sync,
+    SessionRepo: FromRef<S>,
+{
+    type Rejection = SessionRouterError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &S,
+    ) -> Result<Option<Self>, Self::Rejection> {
+        let sessions = SessionRepo::from_ref(state);
+
+        Ok(session_token(parts).and_then(|token| sessions.by_token(&token)))
+    }
+}
+
+impl<S> FromRequestParts<S> for Session
+where
+    S: Send + Sync,
+    SessionRepo: FromRef<S>,
+{
+    type Rejection = SessionRouterError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        <Self as OptionalFromRequestParts<S>>::from_request_parts(parts, state)
+            .await?
+            .ok_or(SessionRouterError::Missing)
+    }
+}
+
+#[derive(Deserialize)]
+struct NicknameBody {
+    #[serde(default)]
+    nickname: Option<String>,
+}
+
+/// Trims the nickname, an empty one clears it.
+fn validate_nickname(nickname: Option<String>) -> Result<Option<String>, SessionRouterError> {
+    let Some(nickname) = nickname else {
+        return Ok(None);
+    };
+    let nickname = nickname.trim();
+
+    if nickname.chars().count() > MAX_NICKNAME_LENGTH || nickname.chars().any(char::is_control) {
+        return Err(SessionRouterError::InvalidNickname);
+    }
+
+    Ok((!nickname.is_empty()).then(|| nickname.to_string()))
+}
+
+#[derive(Serialize)]
+struct NewSession {
+    #[serde(flatten)]
+    session: Session,
+    token: String,
+}
+
+/// Starts a session and sets its cookie, which lasts as long as the session.
+/// The token is also returned for clients that don't keep cookies.
+async fn post_session(
+    State(sessions): State<SessionRepo>,
+    InsecureClientIp(ip): InsecureClientIp,
+    body: Option<Json<NicknameBody>>,
+) -> Result<impl IntoResponse, SessionRouterError> {
+    let nickname = validate_nickname(body.and_then(|Json(body)| body.nickname))?;
+    let (session, token) = sessions.create(nickname, ip)?;
+    let cookie = format!(
+        "{COOKIE_NAME}={token}; Path=/; Max-Age={}; HttpOnly; SameSite=Lax",
+        SESSION_LIFETIME.num_seconds()
+    );
+
+    Ok((
+        StatusCode::CREATED,
+        [(SET_COOKIE, cookie)],
+        Json(NewSession { session, token }),
+    ))
+}
+
+async fn get_own_session(session: Session) -> Json<Session> { Json(session) }
+
+async fn put_own_session(
+    State(sessions): State<SessionRepo>,
+    session: Session,
+    Json(body): Json<NicknameBody>,
+) -> Result<Json<Session>, SessionRouterError> {
+    let nickname = validate_nickname(body.nickname)?;
+    let session = sessions
+        .set_nickname(session.id, nickname)
+        .ok_or(SessionRouterError::NotFound(session.id))?;
+
+    Ok(Json(session))
+}
+
+/// Looks up who painted a led, see [`crate::repo::led::Led::painted_by`].
+async fn get_session(
+    State(sessions): State<SessionRepo>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Session>, SessionRouterError> {
+    let session = sessions.get(id).ok_or(SessionRouterError::NotFound(id))?;
+
+    Ok(Json(session))
+}
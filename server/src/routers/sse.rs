@@ -0,0 +1,200 @@
+use std::{convert::Infallible, sync::Arc, time::Duration};
+
+use axum::{
+    extract::{Query, State},
+    http::HeaderMap,
+    response::sse::{Event, KeepAlive, Sse},
+};
+use futures::{stream, Stream};
+use serde::{Deserialize, Serialize};
+use serde_inline_default::serde_inline_default;
+use tokio::time::sleep;
+
+use super::api::{WithId, BOOT_ID};
+use crate::repo::{
+    canvas::Canvas,
+    led::{Led, LedRepo, LedRepoSnapshot},
+    output::OutputRepo,
+};
+
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum SseMode {
+    /// Every event carries the whole canvas.
+    #[default]
+    Snapshot,
+    /// After the first full snapshot, events only carry the leds that changed.
+    Delta,
+}
+
+#[serde_inline_default]
+#[derive(Deserialize)]
+pub(super) struct SseParams {
+    #[serde_inline_default(false)]
+    colors_only: bool,
+    #[serde_inline_default(100)]
+    snapshot_interval: u64,
+    #[serde(default)]
+    mode: SseMode,
+}
+
+#[derive(Serialize)]
+struct LedsEvent {
+    generation: usize,
+    leds: Vec<WithId<Led>>,
+}
+
+#[derive(Serialize)]
+struct ColorsEvent {
+    generation: usize,
+    colors: Vec<[u8; 3]>,
+}
+
+struct StreamState {
+    canvas: Arc<str>,
+    leds: LedRepo,
+    output: OutputRepo,
+    params: SseParams,
+    last_generation: Option<usize>,
+    last_output_generation: usize,
+    last_snapshot: Option<LedRepoSnapshot>,
+}
+
+/// Server-Sent Events alternative to the websocket, for consumers that can't
+/// upgrade connections. Event ids are the boot id and the led generation, so a
+/// reconnecting client that sends `Last-Event-ID` only receives something once
+/// the canvas changed since, and a full snapshot after a restart.
+pub(super) async fn get_events(
+    Canvas { name, leds, .. }: Canvas,
+    State(output): State<OutputRepo>,
+    Query(params): Query<SseParams>,
+    headers: HeaderMap,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let last_generation = headers
+        .get("last-event-id")
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_event_id);
+
+    let state = StreamState {
+        last_output_generation: output.generation(),
+        canvas: name,
+        leds,
+        output,
+        params: SseParams {
+            snapshot_interval: params.snapshot_interval.max(100),
+            ..params
+        },
+        last_generation,
+        last_snapshot: None,
+    };
+
+    let stream = stream::unfold(state, |mut state| async move {
+        loop {
+            let generation = state.leds.generation();
+            let output_generation = state.output.generation();
+            let output_changed =
+                state.params.colors_only && state.last_output_generation < output_generation;
+
+            if state.last_generation != Some(generation) || output_changed {
+                state.last_output_generation = output_generation;
+                let event = next_event(&mut state).await;
+                return Some((Ok(event), state));
+            }
+
+            sleep(Duration::from_millis(state.params.snapshot_interval)).await;
+        }
+    });
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+async fn next_event(state: &mut StreamState) -> Event {
+    let snapshot = state.leds.snapshot().await;
+    state.last_generation = Some(snapshot.generation);
+
+    let event = if state.params.colors_only {
+        let frame = state
+            .output
+            .canvas_pipeline(&state.canvas)
+            .await
+            .frame(snapshot.leds.iter().map(|led| led.color));
+
+        Event::default().event("colors").json_data(ColorsEvent {
+            generation: snapshot.generation,
+            colors: frame
+                .data
+                .chunks_exact(3)
+                .map(|color| [color[0], color[1], color[2]])
+                .collect(),
+        })
+    } else {
+        let previous = match state.params.mode {
+            SseMode::Delta => state.last_snapshot.as_ref(),
+            SseMode::Snapshot => None,
+        };
+        let leds = snapshot
+            .leds
+            .iter()
+            .enumerate()
+            .filter(|(id, led)| {
+                previous.is_none_or(|previous| {
+                    previous
+                        .leds
+                        .get(*id)
+                        .is_none_or(|previous| previous.last_updated != led.last_updated)
+                })
+            })
+            .map(|(id, led)| WithId::new(id, *led))
+            .collect();
+
+        Event::default()
+            .event(if previous.is_some() {
+                "delta"
+            } else {
+                "snapshot"
+            })
+            .json_data(LedsEvent {
+                generation: snapshot.generation,
+                leds,
+            })
+    };
+
+    let generation = snapshot.generation;
+    if state.params.mode == SseMode::Delta {
+        state.last_snapshot = Some(snapshot);
+    }
+
+    event
+        .expect("led events always serialize")
+        .id(format!("{}-{generation}", *BOOT_ID))
+}
+
+/// The generation of an event id sent by this process. Ids from before a
+/// restart name another boot and are ignored.
+fn parse_event_id(id: &str) -> Option<usize> {
+    let (boot_id, generation) = id.rsplit_once('-')?;
+
+    if boot_id != BOOT_ID.to_string() {
+        return None;
+    }
+
+    generation.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn event_ids_from_this_boot_resume() {
+        assert_eq!(parse_event_id(&format!("{}-42", *BOOT_ID)), Some(42));
+    }
+
+    #[test]
+    fn event_ids_from_another_boot_are_ignored() {
+        let other = uuid::Uuid::new_v4();
+
+        assert_eq!(parse_event_id(&format!("{other}-42")), None);
+        assert_eq!(parse_event_id("42"), None);
+    }
+}
@@ -0,0 +1,77 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use axum_thiserror::ErrorStatus;
+use serde::Serialize;
+
+use crate::{
+    repo::{
+        session::{Session, SessionRepo, SessionRepoError},
+        team::{Team, TeamRepo, TeamScore},
+    },
+    state::AppState,
+};
+
+pub fn get_router() -> Router<AppState> {
+    Router::new()
+        .route("/teams", get(get_teams))
+        .route("/teams/leaderboard", get(get_leaderboard))
+        .route("/teams/{name}/join", post(post_join))
+}
+
+#[derive(thiserror::Error, Debug, ErrorStatus)]
+pub enum TeamRouterError {
+    #[error("Team '{0}' does not exist")]
+    #[status(StatusCode::NOT_FOUND)]
+    NotFound(String),
+    #[error("Session with id {0} does not exist")]
+    #[status(StatusCode::NOT_FOUND)]
+    NoSession(uuid::Uuid),
+    #[error("Already on team '{0}'")]
+    #[status(StatusCode::CONFLICT)]
+    AlreadyInTeam(String),
+}
+
+#[derive(Serialize)]
+struct TeamsResponse {
+    /// Teams only play on this canvas.
+    canvas: Option<String>,
+    teams: Vec<Team>,
+}
+
+async fn get_teams(State(teams): State<TeamRepo>) -> Json<TeamsResponse> {
+    Json(TeamsResponse {
+        canvas: teams.is_active().then(|| teams.canvas().to_string()),
+        teams: teams.teams().to_vec(),
+    })
+}
+
+async fn get_leaderboard(State(teams): State<TeamRepo>) -> Json<Vec<TeamScore>> {
+    Json(teams.leaderboard())
+}
+
+/// Sessions stay on the first team they join.
+async fn post_join(
+    State(teams): State<TeamRepo>,
+    State(sessions): State<SessionRepo>,
+    Path(name): Path<String>,
+    session: Session,
+) -> Result<Json<Session>, TeamRouterError> {
+    let team = teams
+        .get(&name)
+        .ok_or_else(|| TeamRouterError::NotFound(name.clone()))?;
+
+    let session = sessions
+        .join_team(session.id, team.name.clone())
+        .map_err(|err| match err {
+            SessionRepoError::NotFound(id) => TeamRouterError::NoSession(id),
+            SessionRepoError::AlreadyInTeam(team) => {
+                TeamRouterError::AlreadyInTeam(team.to_string())
+            }
+        })?;
+
+    Ok(Json(session))
+}
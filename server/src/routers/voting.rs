@@ -0,0 +1,77 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{get, post},
+    Form, Json, Router,
+};
+use axum_thiserror::ErrorStatus;
+use serde::Serialize;
+
+use crate::{
+    palette::{apply_palette, PaletteError},
+    repo::{
+        canvas::CanvasRepo,
+        session::Session,
+        voting::{Round, RoundResults, VotingRepo},
+    },
+    state::AppState,
+    types::Color,
+};
+
+pub fn get_router() -> Router<AppState> {
+    Router::new()
+        .route("/voting", get(get_voting))
+        .route("/voting/leds/{id}", post(post_vote))
+}
+
+#[derive(thiserror::Error, Debug, ErrorStatus)]
+pub enum VotingRouterError {
+    #[error("Voting mode is off")]
+    #[status(StatusCode::NOT_FOUND)]
+    Off,
+    #[error("Led with id {0} does not exist")]
+    #[status(StatusCode::NOT_FOUND)]
+    NotFound(usize),
+    #[error(transparent)]
+    #[status(StatusCode::UNPROCESSABLE_ENTITY)]
+    Palette(#[from] PaletteError),
+}
+
+#[derive(Serialize)]
+struct VotingResponse {
+    /// Votes only go to this canvas.
+    canvas: Option<String>,
+    round: Option<Round>,
+    /// Results of the latest round that ended.
+    results: Option<RoundResults>,
+}
+
+async fn get_voting(State(voting): State<VotingRepo>) -> Json<VotingResponse> {
+    Json(VotingResponse {
+        canvas: voting.canvas().map(str::to_string),
+        round: voting.is_active().then(|| voting.round()),
+        results: voting.results(),
+    })
+}
+
+/// Votes for a led of the voted canvas, sessions get one vote per led and
+/// round.
+async fn post_vote(
+    State(voting): State<VotingRepo>,
+    State(canvases): State<CanvasRepo>,
+    Path(id): Path<usize>,
+    session: Session,
+    Form(color): Form<Color>,
+) -> Result<Json<Round>, VotingRouterError> {
+    let canvas = voting
+        .canvas()
+        .and_then(|name| canvases.get(name))
+        .ok_or(VotingRouterError::Off)?;
+
+    if id >= canvas.leds.led_count() {
+        return Err(VotingRouterError::NotFound(id));
+    }
+    let color = apply_palette(canvas.palette.as_deref(), color)?;
+
+    Ok(Json(voting.vote(session.id, id, color)))
+}
@@ -0,0 +1,96 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{delete, get},
+    Json, Router,
+};
+use axum_thiserror::ErrorStatus;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    repo::webhook::{Delivery, Webhook, WebhookRepo},
+    state::AppState,
+    webhooks::is_public_target,
+};
+
+pub fn get_router() -> Router<AppState> {
+    Router::new()
+        .route("/webhooks", get(get_webhooks).post(post_webhook))
+        .route("/webhooks/{id}", delete(delete_webhook))
+        .route("/webhooks/{id}/deliveries", get(get_deliveries))
+}
+
+#[derive(thiserror::Error, Debug, ErrorStatus)]
+pub enum WebhookRouterError {
+    #[error("Webhook with id {0} does not exist")]
+    #[status(StatusCode::NOT_FOUND)]
+    NotFound(Uuid),
+    #[error("'{0}' is not a valid http(s) url")]
+    #[status(StatusCode::UNPROCESSABLE_ENTITY)]
+    InvalidUrl(String),
+    #[error("'{0}' does not resolve to a public address")]
+    #[status(StatusCode::UNPROCESSABLE_ENTITY)]
+    PrivateTarget(String),
+}
+
+#[derive(Serialize)]
+struct WebhookResponse {
+    id: Uuid,
+    #[serde(flatten)]
+    webhook: Webhook,
+}
+
+async fn get_webhooks(State(webhooks): State<WebhookRepo>) -> Json<Vec<WebhookResponse>> {
+    Json(
+        webhooks
+            .list()
+            .await
+            .into_iter()
+            .map(|(id, webhook)| WebhookResponse { id, webhook })
+            .collect(),
+    )
+}
+
+async fn post_webhook(
+    State(webhooks): State<WebhookRepo>,
+    Json(webhook): Json<Webhook>,
+) -> Result<(StatusCode, Json<WebhookResponse>), WebhookRouterError> {
+    let url = match reqwest::Url::parse(&webhook.url) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => url,
+        _ => return Err(WebhookRouterError::InvalidUrl(webhook.url)),
+    };
+    // Deliveries to names are checked again on every lookup, this refuses
+    // addresses and names that already point inside early.
+    if !webhooks.allows_private_targets() && !is_public_target(&url).await {
+        return Err(WebhookRouterError::PrivateTarget(webhook.url));
+    }
+
+    let id = webhooks.insert(webhook.clone()).await;
+
+    Ok((StatusCode::CREATED, Json(WebhookResponse { id, webhook })))
+}
+
+async fn delete_webhook(
+    State(webhooks): State<WebhookRepo>,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, WebhookRouterError> {
+    webhooks
+        .remove(id)
+        .await
+        .ok_or(WebhookRouterError::NotFound(id))?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn get_deliveries(
+    State(webhooks): State<WebhookRepo>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Vec<Delivery>>, WebhookRouterError> {
+    let deliveries = webhooks
+        .deliveries(id)
+        .await
+        .ok_or(WebhookRouterError::NotFound(id))?;
+
+    Ok(Json(deliveries))
+}
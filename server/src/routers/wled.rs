@@ -0,0 +1,485 @@
+//! Enough of the WLED JSON API for WLED-aware apps to control the default
+//! canvas. Scenes are exposed as presets, numbered from 1 in name order.
+
+use std::{collections::BTreeMap, sync::LazyLock, time::Instant};
+
+use axum::{
+    extract::State,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    events::EventBus,
+    gate::{WriteError, WriteGate},
+    repo::{canvas::CanvasRepo, output::OutputRepo},
+    routers::scene::apply_scene,
+    state::AppState,
+    types::Color,
+};
+
+/// The WLED release whose API is emulated, apps gate features on it.
+const WLED_VERSION: &str = "0.14.0";
+const WLED_BUILD: u32 = 2310130;
+const EFFECTS: &[&str] = &["Solid"];
+const PALETTES: &[&str] = &["Default"];
+
+static STARTED_AT: LazyLock<Instant> = LazyLock::new(Instant::now);
+
+pub fn get_router() -> Router<AppState> {
+    LazyLock::force(&STARTED_AT);
+
+    Router::new()
+        .route("/json", get(get_json).post(post_state))
+        .route("/json/si", get(get_state_and_info).post(post_state))
+        .route("/json/state", get(get_state).post(post_state))
+        .route("/json/info", get(get_info))
+        .route("/json/eff", get(get_effects))
+        .route("/json/pal", get(get_palettes))
+        .route("/presets.json", get(get_presets))
+}
+
+#[derive(Serialize)]
+struct WledState {
+    on: bool,
+    bri: u8,
+    transition: u8,
+    ps: i32,
+    pl: i32,
+    mainseg: u8,
+    seg: [WledSegment; 1],
+}
+
+#[derive(Serialize)]
+struct WledSegment {
+    id: u8,
+    start: usize,
+    stop: usize,
+    len: usize,
+    col: [[u8; 3]; 3],
+    fx: u8,
+    sx: u8,
+    ix: u8,
+    pal: u8,
+    on: bool,
+    bri: u8,
+    sel: bool,
+}
+
+#[derive(Serialize)]
+struct WledInfo {
+    ver: &'static str,
+    vid: u32,
+    leds: WledLeds,
+    str: bool,
+    name: &'static str,
+    udpport: u16,
+    live: bool,
+    lm: &'static str,
+    lip: &'static str,
+    ws: i32,
+    fxcount: usize,
+    palcount: usize,
+    wifi: WledWifi,
+    arch: &'static str,
+    core: &'static str,
+    freeheap: u32,
+    uptime: u64,
+    brand: &'static str,
+    product: &'static str,
+    mac: &'static str,
+    ip: &'static str,
+}
+
+#[derive(Serialize)]
+struct WledLeds {
+    count: usize,
+    pwr: u32,
+    fps: u8,
+    maxpwr: u32,
+    maxseg: u8,
+    seglc: [u8; 1],
+    lc: u8,
+    rgbw: bool,
+    wv: u8,
+    cct: u8,
+}
+
+#[derive(Serialize)]
+struct WledWifi {
+    bssid: &'static str,
+    rssi: i32,
+    signal: u8,
+    channel: u8,
+}
+
+async fn wled_state(canvases: &CanvasRepo, output: &OutputRepo) -> WledState {
+    let canvas = canvases.default_canvas();
+    let light = output.light(&canvas.name).await;
+    let led_count = canvas.leds.led_count();
+    // Canvases aren't single colored, the first led stands in for the rest.
+    let color = canvas
+        .leds
+        .get(0)
+        .await
+        .map(|led| led.color)
+        .unwrap_or(Color {
+            red: 0,
+            green: 0,
+            blue: 0,
+        });
+
+    WledState {
+        on: light.on,
+        bri: light.brightness,
+        transition: 0,
+        ps: -1,
+        pl: -1,
+        mainseg: 0,
+        seg: [WledSegment {
+            id: 0,
+            start: 0,
+            stop: led_count,
+            len: led_count,
+            col: [[color.red, color.green, color.blue], [0; 3], [0; 3]],
+            fx: 0,
+            sx: 128,
+            ix: 128,
+            pal: 0,
+            on: light.on,
+            bri: 255,
+            sel: true,
+        }],
+    }
+}
+
+async fn wled_info(canvases: &CanvasRepo, output: &OutputRepo) -> WledInfo {
+    let canvas = canvases.default_canvas();
+    let snapshot = canvas.leds.snapshot().await;
+    let frame = output
+        .canvas_pipeline(&canvas.name)
+        .await
+        .frame(snapshot.leds.into_iter().map(|led| led.color));
+
+    WledInfo {
+        ver: WLED_VERSION,
+        vid: WLED_BUILD,
+        leds: WledLeds {
+            count: canvas.leds.led_count(),
+            pwr: frame.power.output_milliamps as u32,
+            fps: 0,
+            maxpwr: frame.power.budget_milliamps.unwrap_or(0.0) as u32,
+            maxseg: 1,
+            seglc: [1],
+            lc: 1,
+            rgbw: false,
+            wv: 0,
+            cct: 0,
+        },
+        str: false,
+        name: env!("CARGO_CRATE_NAME"),
+        udpport: 0,
+        live: canvas.leds.has_holds(),
+        lm: "",
+        lip: "",
+        ws: -1,
+        fxcount: EFFECTS.len(),
+        palcount: PALETTES.len(),
+        wifi: WledWifi {
+            bssid: "",
+            rssi: 0,
+            signal: 100,
+            channel: 0,
+        },
+        arch: env!("CARGO_CRATE_NAME"),
+        core: env!("CARGO_PKG_VERSION"),
+        freeheap: 0,
+        uptime: STARTED_AT.elapsed().as_secs(),
+        brand: "WLED",
+        product: "FOSS",
+        mac: "000000000000",
+        ip: "",
+    }
+}
+
+async fn get_json(
+    State(canvases): State<CanvasRepo>,
+    State(output): State<OutputRepo>,
+) -> impl IntoResponse {
+    Json(json!({
+        "state": wled_state(&canvases, &output).await,
+        "info": wled_info(&canvases, &output).await,
+        "effects": EFFECTS,
+        "palettes": PALETTES,
+    }))
+}
+
+async fn get_state_and_info(
+    State(canvases): State<CanvasRepo>,
+    State(output): State<OutputRepo>,
+) -> impl IntoResponse {
+    Json(json!({
+        "state": wled_state(&canvases, &output).await,
+        "info": wled_info(&canvases, &output).await,
+    }))
+}
+
+async fn get_state(
+    State(canvases): State<CanvasRepo>,
+    State(output): State<OutputRepo>,
+) -> Json<WledState> {
+    Json(wled_state(&canvases, &output).await)
+}
+
+async fn get_info(
+    State(canvases): State<CanvasRepo>,
+    State(output): State<OutputRepo>,
+) -> Json<WledInfo> {
+    Json(wled_info(&canvases, &output).await)
+}
+
+async fn get_effects() -> Json<&'static [&'static str]> { Json(EFFECTS) }
+
+async fn get_palettes() -> Json<&'static [&'static str]> { Json(PALETTES) }
+
+/// WLED keys presets by id, with an empty entry for the unused id 0.
+async fn get_presets(State(canvases): State<CanvasRepo>) -> impl IntoResponse {
+    let names = canvases.default_canvas().scenes.names().await;
+    let mut presets = BTreeMap::from([("0".to_string(), json!({}))]);
+    for (index, name) in names.into_iter().enumerate() {
+        presets.insert((index + 1).to_string(), json!({ "n": name }));
+    }
+
+    Json(presets)
+}
+
+/// `true`/`false`, or `"t"` to toggle.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Switch {
+    Set(bool),
+    Toggle(String),
+}
+
+/// WLED accepts colors as `[r, g, b(, w)]` arrays or hex strings.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum WledColor {
+    Channels(Vec<u8>),
+    Hex(String),
+}
+
+impl WledColor {
+    fn to_color(&self) -> Option<Color> {
+        match self {
+            WledColor::Channels(channels) => match channels[..] {
+                [red, green, blue, ..] => Some(Color { red, green, blue }),
+                _ => None,
+            },
+            WledColor::Hex(hex) => hex.get(..6)?.parse().ok(),
+        }
+    }
+}
+
+/// Entries of a segment's `i` array: a color on its own goes to the next led,
+/// after one index to that led, after two indexes to the range between them.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Individual {
+    Index(usize),
+    Color(WledColor),
+}
+
+#[derive(Deserialize)]
+struct SegmentUpdate {
+    col: Option<Vec<WledColor>>,
+    i: Option<Vec<Individual>>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Segments {
+    One(SegmentUpdate),
+    Many(Vec<SegmentUpdate>),
+}
+
+#[derive(Deserialize)]
+struct StateUpdate {
+    on: Option<Switch>,
+    bri: Option<u8>,
+    ps: Option<i32>,
+    seg: Option<Segments>,
+    #[serde(default)]
+    v: bool,
+}
+
+async fn post_state(
+    State(canvases): State<CanvasRepo>,
+    State(output): State<OutputRepo>,
+    State(events): State<EventBus>,
+    State(gate): State<WriteGate>,
+    Json(update): Json<StateUpdate>,
+) -> Response {
+    let canvas = canvases.default_canvas();
+
+    // Switching and dimming only affect the default canvas, and count as a
+    // write to all of it.
+    if update.on.is_some() || update.bri.is_some() {
+        match gate.check_bulk::<WriteError>(&canvas, []) {
+            Ok(()) => {
+                let mut light = output.light(&canvas.name).await;
+                match update.on {
+                    Some(Switch::Set(on)) => light.on = on,
+                    Some(Switch::Toggle(value)) if value == "t" => light.on = !light.on,
+                    _ => {}
+                }
+                if let Some(brightness) = update.bri {
+                    light.brightness = brightness;
+                }
+                output.set_light(canvas.name.clone(), light).await;
+            }
+            Err(err) => tracing::debug!("Refused WLED on/brightness: {err}"),
+        }
+    }
+
+    if let Some(preset) = update.ps.and_then(|preset| usize::try_from(preset).ok()) {
+        let names = canvas.scenes.names().await;
+        if let Some(name) = preset.checked_sub(1).and_then(|index| names.get(index)) {
+            if let Err(err) = apply_scene(&canvas, name, &events, &gate).await {
+                tracing::debug!("Refused WLED preset: {err}");
+            }
+        }
+    }
+
+    let segments = match update.seg {
+        Some(Segments::One(segment)) => vec![segment],
+        Some(Segments::Many(segments)) => segments,
+        None => Vec::new(),
+    };
+    for segment in segments {
+        if let Some(mut color) = segment
+            .col
+            .as_ref()
+            .and_then(|colors| colors.first())
+            .and_then(WledColor::to_color)
+        {
+            match gate.check_bulk::<WriteError>(&canvas, [&mut color]) {
+                Ok(()) => canvas.leds.fill(color).await,
+                Err(err) => tracing::debug!("Refused WLED segment color: {err}"),
+            }
+        }
+
+        if let Some(individual) = &segment.i {
+            let mut colors = individual_colors(individual, canvas.leds.led_count());
+            match gate.check_bulk::<WriteError>(&canvas, colors.iter_mut().map(|(_, color)| color))
+            {
+                // Ids are clamped to the canvas by `individual_colors`.
+                Ok(()) => {
+                    let _ = canvas.leds.set_many(colors).await;
+                }
+                Err(err) => tracing::debug!("Refused WLED individual leds: {err}"),
+            }
+        }
+    }
+
+    if update.v {
+        Json(wled_state(&canvases, &output).await).into_response()
+    } else {
+        Json(json!({ "success": true })).into_response()
+    }
+}
+
+fn individual_colors(individual: &[Individual], led_count: usize) -> Vec<(usize, Color)> {
+    let mut colors = Vec::new();
+    let mut next = 0usize;
+    let mut indexes = Vec::with_capacity(2);
+
+    for entry in individual {
+        match entry {
+            Individual::Index(index) => indexes.push(*index),
+            Individual::Color(color) => {
+                let range = match indexes[..] {
+                    [] => next..next.saturating_add(1),
+                    [id] => id..id.saturating_add(1),
+                    [.., start, stop] => start..stop,
+                };
+                indexes.clear();
+                next = range.end;
+
+                if let Some(color) = color.to_color() {
+                    colors.extend(
+                        (range.start.min(led_count)..range.end.min(led_count))
+                            .map(|id| (id, color)),
+                    );
+                }
+            }
+        }
+    }
+
+    colors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: Color = Color {
+        red: 255,
+        green: 0,
+        blue: 0,
+    };
+    const BLUE: Color = Color {
+        red: 0,
+        green: 0,
+        blue: 255,
+    };
+
+    fn colors(individual: serde_json::Value, led_count: usize) -> Vec<(usize, Color)> {
+        let individual: Vec<Individual> = serde_json::from_value(individual).unwrap();
+        individual_colors(&individual, led_count)
+    }
+
+    #[test]
+    fn colors_on_their_own_go_to_consecutive_leds() {
+        assert_eq!(
+            colors(json!(["FF0000", [0, 0, 255]]), 10),
+            vec![(0, RED), (1, BLUE)]
+        );
+    }
+
+    #[test]
+    fn an_index_moves_the_next_led() {
+        assert_eq!(
+            colors(json!([5, "FF0000", "0000FF"]), 10),
+            vec![(5, RED), (6, BLUE)]
+        );
+    }
+
+    #[test]
+    fn two_indexes_fill_the_range_between_them() {
+        assert_eq!(
+            colors(json!([1, 3, "FF0000", "0000FF"]), 10),
+            vec![(1, RED), (2, RED), (3, BLUE)]
+        );
+    }
+
+    #[test]
+    fn ranges_are_clipped_to_the_canvas() {
+        assert_eq!(
+            colors(json!([8, 12, "FF0000", "0000FF"]), 10),
+            vec![(8, RED), (9, RED)]
+        );
+        assert!(colors(json!([usize::MAX, "FF0000", "0000FF"]), 10).is_empty());
+    }
+
+    #[test]
+    fn invalid_colors_are_skipped_but_take_their_led() {
+        assert_eq!(
+            colors(json!(["nope", [1, 2], "0000FF"]), 10),
+            vec![(2, BLUE)]
+        );
+    }
+}
@@ -0,0 +1,56 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use tokio::time::interval;
+
+use crate::{
+    repo::{canvas::CanvasRepo, schedule::ScheduleRepo},
+    solar::Coordinates,
+};
+
+const TICK: Duration = Duration::from_secs(5);
+
+/// Periodically fires schedules whose trigger passed since the previous tick.
+pub async fn run_scheduler(
+    schedules: ScheduleRepo,
+    canvases: CanvasRepo,
+    coordinates: Option<Coordinates>,
+) {
+    let mut ticker = interval(TICK);
+    let mut last_tick = Utc::now();
+
+    loop {
+        ticker.tick().await;
+        let now = Utc::now();
+
+        for (id, schedule) in schedules.list().await {
+            let fired = schedule
+                .trigger
+                .next_after(last_tick, coordinates)
+                .is_some_and(|time| time <= now);
+
+            if !fired {
+                continue;
+            }
+
+            let canvas = match &schedule.canvas {
+                Some(name) => canvases.get(name),
+                None => Some(canvases.default_canvas()),
+            };
+
+            match canvas {
+                Some(canvas) => {
+                    tracing::info!(
+                        "Schedule {id} fired, filling canvas '{}' with {:?}",
+                        canvas.name,
+                        schedule.color
+                    );
+                    canvas.leds.fill(schedule.color).await;
+                }
+                None => tracing::warn!("Schedule {id} fired for a canvas that does not exist"),
+            }
+        }
+
+        last_tick = now;
+    }
+}
@@ -0,0 +1,177 @@
+use std::f64::consts::PI;
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug)]
+pub struct Coordinates {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SolarEvent {
+    CivilDawn,
+    Sunrise,
+    Sunset,
+    CivilDusk,
+}
+
+impl SolarEvent {
+    /// Zenith angle (in degrees) of the sun's center when the event happens.
+    fn zenith(self) -> f64 {
+        match self {
+            SolarEvent::Sunrise | SolarEvent::Sunset => 90.833,
+            SolarEvent::CivilDawn | SolarEvent::CivilDusk => 96.0,
+        }
+    }
+
+    fn is_morning(self) -> bool { matches!(self, SolarEvent::CivilDawn | SolarEvent::Sunrise) }
+}
+
+#[derive(Serialize, Clone, Copy, Debug)]
+pub struct SolarTimes {
+    pub civil_dawn: Option<DateTime<Utc>>,
+    pub sunrise: Option<DateTime<Utc>>,
+    pub sunset: Option<DateTime<Utc>>,
+    pub civil_dusk: Option<DateTime<Utc>>,
+}
+
+impl SolarTimes {
+    pub fn on(date: NaiveDate, coordinates: Coordinates) -> Self {
+        Self {
+            civil_dawn: solar_event_time(date, coordinates, SolarEvent::CivilDawn),
+            sunrise: solar_event_time(date, coordinates, SolarEvent::Sunrise),
+            sunset: solar_event_time(date, coordinates, SolarEvent::Sunset),
+            civil_dusk: solar_event_time(date, coordinates, SolarEvent::CivilDusk),
+        }
+    }
+}
+
+/// Computes when `event` happens on the given (UTC) date using the NOAA
+/// approximation, which is accurate to within a minute or two for
+/// non-polar latitudes.
+///
+/// Returns `None` when the event does not happen that day (polar day/night).
+pub fn solar_event_time(
+    date: NaiveDate,
+    Coordinates {
+        latitude,
+        longitude,
+    }: Coordinates,
+    event: SolarEvent,
+) -> Option<DateTime<Utc>> {
+    let days_in_year = if date.leap_year() { 366.0 } else { 365.0 };
+    let fractional_year = 2.0 * PI / days_in_year * (date.ordinal0() as f64);
+
+    let equation_of_time = 229.18
+        * (0.000075 + 0.001868 * fractional_year.cos()
+            - 0.032077 * fractional_year.sin()
+            - 0.014615 * (2.0 * fractional_year).cos()
+            - 0.040849 * (2.0 * fractional_year).sin());
+
+    let declination = 0.006918 - 0.399912 * fractional_year.cos()
+        + 0.070257 * fractional_year.sin()
+        - 0.006758 * (2.0 * fractional_year).cos()
+        + 0.000907 * (2.0 * fractional_year).sin()
+        - 0.002697 * (3.0 * fractional_year).cos()
+        + 0.00148 * (3.0 * fractional_year).sin();
+
+    let latitude = latitude.to_radians();
+    let cos_hour_angle = event.zenith().to_radians().cos() / (latitude.cos() * declination.cos())
+        - latitude.tan() * declination.tan();
+
+    if !(-1.0..=1.0).contains(&cos_hour_angle) {
+        return None;
+    }
+
+    let hour_angle = cos_hour_angle.acos().to_degrees();
+    let hour_angle = if event.is_morning() {
+        hour_angle
+    } else {
+        -hour_angle
+    };
+
+    let minutes = 720.0 - 4.0 * (longitude + hour_angle) - equation_of_time;
+    let midnight = date.and_hms_opt(0, 0, 0)?.and_utc();
+
+    Some(midnight + Duration::seconds((minutes * 60.0).round() as i64))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    const LONDON: Coordinates = Coordinates {
+        latitude: 51.5074,
+        longitude: -0.1278,
+    };
+
+    fn assert_close(actual: Option<DateTime<Utc>>, expected: DateTime<Utc>) {
+        let actual = actual.expect("event should happen");
+        let off_by = (actual - expected).num_seconds().abs();
+        assert!(off_by <= 120, "{actual} is {off_by}s off {expected}");
+    }
+
+    fn on(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn matches_noaa_at_summer_solstice() {
+        let date = on(2024, 6, 20);
+        let at = |hour, minute| Utc.with_ymd_and_hms(2024, 6, 20, hour, minute, 0).unwrap();
+
+        assert_close(
+            solar_event_time(date, LONDON, SolarEvent::Sunrise),
+            at(3, 43),
+        );
+        assert_close(
+            solar_event_time(date, LONDON, SolarEvent::Sunset),
+            at(20, 21),
+        );
+    }
+
+    #[test]
+    fn matches_noaa_at_winter_solstice() {
+        let date = on(2024, 12, 21);
+        let at = |hour, minute| Utc.with_ymd_and_hms(2024, 12, 21, hour, minute, 0).unwrap();
+
+        assert_close(
+            solar_event_time(date, LONDON, SolarEvent::CivilDawn),
+            at(7, 24),
+        );
+        assert_close(
+            solar_event_time(date, LONDON, SolarEvent::Sunrise),
+            at(8, 4),
+        );
+        assert_close(
+            solar_event_time(date, LONDON, SolarEvent::Sunset),
+            at(15, 53),
+        );
+        assert_close(
+            solar_event_time(date, LONDON, SolarEvent::CivilDusk),
+            at(16, 33),
+        );
+    }
+
+    #[test]
+    fn has_no_sunrise_during_polar_day_or_night() {
+        let tromso = Coordinates {
+            latitude: 69.6492,
+            longitude: 18.9553,
+        };
+
+        assert_eq!(
+            solar_event_time(on(2024, 6, 21), tromso, SolarEvent::Sunrise),
+            None
+        );
+        assert_eq!(
+            solar_event_time(on(2024, 12, 21), tromso, SolarEvent::Sunrise),
+            None
+        );
+    }
+}
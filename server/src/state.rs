@@ -1,12 +1,77 @@
 use axum::extract::FromRef;
 
-use crate::repo::led::LedRepo;
+use crate::{
+    events::EventBus,
+    gate::WriteGate,
+    repo::{
+        canvas::CanvasRepo, client::ClientRepo, device::DeviceRepo, output::OutputRepo,
+        schedule::ScheduleRepo, session::SessionRepo, team::TeamRepo, voting::VotingRepo,
+        webhook::WebhookRepo,
+    },
+    solar::Coordinates,
+};
 
 #[derive(Clone)]
 pub struct AppState {
-    pub leds: LedRepo,
+    pub canvases: CanvasRepo,
+    pub schedules: ScheduleRepo,
+    pub output: OutputRepo,
+    pub coordinates: Option<Coordinates>,
+    pub events: EventBus,
+    pub webhooks: WebhookRepo,
+    pub devices: DeviceRepo,
+    pub clients: ClientRepo,
+    pub sessions: SessionRepo,
+    pub teams: TeamRepo,
+    pub voting: VotingRepo,
 }
 
-impl FromRef<AppState> for LedRepo {
-    fn from_ref(state: &AppState) -> Self { state.leds.clone() }
+impl FromRef<AppState> for CanvasRepo {
+    fn from_ref(state: &AppState) -> Self { state.canvases.clone() }
+}
+
+impl FromRef<AppState> for ScheduleRepo {
+    fn from_ref(state: &AppState) -> Self { state.schedules.clone() }
+}
+
+impl FromRef<AppState> for OutputRepo {
+    fn from_ref(state: &AppState) -> Self { state.output.clone() }
+}
+
+impl FromRef<AppState> for Option<Coordinates> {
+    fn from_ref(state: &AppState) -> Self { state.coordinates }
+}
+
+impl FromRef<AppState> for EventBus {
+    fn from_ref(state: &AppState) -> Self { state.events.clone() }
+}
+
+impl FromRef<AppState> for WebhookRepo {
+    fn from_ref(state: &AppState) -> Self { state.webhooks.clone() }
+}
+
+impl FromRef<AppState> for DeviceRepo {
+    fn from_ref(state: &AppState) -> Self { state.devices.clone() }
+}
+
+impl FromRef<AppState> for ClientRepo {
+    fn from_ref(state: &AppState) -> Self { state.clients.clone() }
+}
+
+impl FromRef<AppState> for SessionRepo {
+    fn from_ref(state: &AppState) -> Self { state.sessions.clone() }
+}
+
+impl FromRef<AppState> for TeamRepo {
+    fn from_ref(state: &AppState) -> Self { state.teams.clone() }
+}
+
+impl FromRef<AppState> for VotingRepo {
+    fn from_ref(state: &AppState) -> Self { state.voting.clone() }
+}
+
+impl FromRef<AppState> for WriteGate {
+    fn from_ref(state: &AppState) -> Self {
+        WriteGate::new(state.teams.clone(), state.voting.clone())
+    }
 }
@@ -0,0 +1,41 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use tokio::time::{interval, Instant, MissedTickBehavior};
+
+use crate::repo::{
+    canvas::Canvas, presence::PresenceMessage, session::SessionRepo, team::TeamRepo,
+};
+
+const TICK: Duration = Duration::from_secs(1);
+
+/// Scores the teams by the leds they own on the played canvas, broadcasting
+/// the scores to its presence clients every tick.
+pub async fn run_team_scoring(teams: TeamRepo, canvas: Canvas, sessions: SessionRepo) {
+    let mut ticker = interval(TICK);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    let mut last_tick = Instant::now();
+
+    loop {
+        ticker.tick().await;
+        let elapsed = last_tick.elapsed();
+        last_tick = Instant::now();
+
+        let session_teams = sessions.teams();
+        let mut members: HashMap<Arc<str>, usize> = HashMap::new();
+        for team in session_teams.values() {
+            *members.entry(team.clone()).or_default() += 1;
+        }
+
+        let mut leds_owned: HashMap<Arc<str>, usize> = HashMap::new();
+        for led in canvas.leds.snapshot().await.leds {
+            if let Some(team) = led.painted_by.and_then(|id| session_teams.get(&id)) {
+                *leds_owned.entry(team.clone()).or_default() += 1;
+            }
+        }
+
+        let scores = teams.record(&leds_owned, &members, elapsed);
+        canvas
+            .presence
+            .broadcast(PresenceMessage::Scores { teams: scores });
+    }
+}
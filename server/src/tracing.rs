@@ -1,8 +1,12 @@
 use std::collections::HashMap;
 
+use axum::http::{
+    header::{AUTHORIZATION, COOKIE, PROXY_AUTHORIZATION},
+    HeaderMap, HeaderValue, Request, Uri,
+};
 use opentelemetry::{trace::TracerProvider, KeyValue};
 use opentelemetry_otlp::{WithExportConfig, WithHttpConfig};
-use tracing::level_filters::LevelFilter;
+use tracing::{level_filters::LevelFilter, Span};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
 pub struct TracingConfig<'a> {
@@ -55,14 +59,25 @@ pub fn setup_tracing(
         let span_exporter = opentelemetry_otlp::SpanExporter::builder()
             .with_http()
             .with_endpoint(format!("{}/v1/traces", otlp_endpoint))
-            .with_headers(headers)
+            .with_headers(headers.clone())
             .build()?;
         let span_provider = opentelemetry_sdk::trace::SdkTracerProvider::builder()
             .with_batch_exporter(span_exporter)
-            .with_resource(resource)
+            .with_resource(resource.clone())
             .build();
         let tracer = span_provider.tracer("controlmylights");
 
+        let metric_exporter = opentelemetry_otlp::MetricExporter::builder()
+            .with_http()
+            .with_endpoint(format!("{}/v1/metrics", otlp_endpoint))
+            .with_headers(headers)
+            .build()?;
+        let meter_provider = opentelemetry_sdk::metrics::SdkMeterProvider::builder()
+            .with_periodic_exporter(metric_exporter)
+            .with_resource(resource)
+            .build();
+        opentelemetry::global::set_meter_provider(meter_provider);
+
         registry
             .with(tracing_opentelemetry::layer().with_tracer(tracer))
             .with(
@@ -89,3 +104,85 @@ pub fn setup_tracing(
 
     Ok(())
 }
+
+/// Query parameters left out of logged uris, since they carry credentials.
+const SECRET_PARAMS: &[&str] = &["session"];
+
+/// Span for a request like [`tower_http::trace::DefaultMakeSpan`] with
+/// headers, minus the credentials: session tokens end up in the `Cookie` and
+/// `Authorization` headers and, for websockets, the query string.
+pub fn make_request_span<B>(request: &Request<B>) -> Span {
+    tracing::info_span!(
+        "request",
+        method = %request.method(),
+        uri = %redact_uri(request.uri()),
+        version = ?request.version(),
+        headers = ?redact_headers(request.headers()),
+    )
+}
+
+fn redact_uri(uri: &Uri) -> String {
+    let Some(query) = uri.query() else {
+        return uri.to_string();
+    };
+
+    let query: Vec<&str> = query
+        .split('&')
+        .filter(|pair| {
+            let name = pair.split_once('=').map_or(*pair, |(name, _)| name);
+            !SECRET_PARAMS.contains(&name)
+        })
+        .collect();
+
+    if query.is_empty() {
+        uri.path().to_string()
+    } else {
+        format!("{}?{}", uri.path(), query.join("&"))
+    }
+}
+
+fn redact_headers(headers: &HeaderMap) -> HeaderMap {
+    let mut headers = headers.clone();
+    for name in [AUTHORIZATION, COOKIE, PROXY_AUTHORIZATION] {
+        if headers.contains_key(&name) {
+            headers.insert(name, HeaderValue::from_static("[redacted]"));
+        }
+    }
+
+    headers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn session_query_parameters_are_stripped() {
+        let uri = |uri: &str| redact_uri(&uri.parse().unwrap());
+
+        assert_eq!(
+            uri("/api/leds/ws?session=secret&colors_only=true"),
+            "/api/leds/ws?colors_only=true"
+        );
+        assert_eq!(uri("/api/leds/ws?session=secret"), "/api/leds/ws");
+        assert_eq!(
+            uri("/api/leds?canvas=sessions"),
+            "/api/leds?canvas=sessions"
+        );
+        assert_eq!(uri("/api/leds"), "/api/leds");
+    }
+
+    #[test]
+    fn credentials_are_redacted_from_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer secret"));
+        headers.append(COOKIE, HeaderValue::from_static("session=secret"));
+        headers.append(COOKIE, HeaderValue::from_static("theme=dark"));
+        headers.insert("user-agent", HeaderValue::from_static("curl"));
+
+        let redacted = format!("{:?}", redact_headers(&headers));
+
+        assert!(!redacted.contains("secret"));
+        assert!(redacted.contains("curl"));
+    }
+}
@@ -1,8 +1,55 @@
+use std::str::FromStr;
+
 use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize, Serialize, Clone, Copy, Debug)]
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Color {
     pub red: u8,
     pub green: u8,
     pub blue: u8,
 }
+
+#[derive(thiserror::Error, Debug)]
+#[error("'{0}' is not a hex color like #ff8800")]
+pub struct ParseColorError(String);
+
+impl FromStr for Color {
+    type Err = ParseColorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.strip_prefix('#').unwrap_or(s);
+        let channel = |index: usize| {
+            hex.get(index..index + 2)
+                .and_then(|channel| u8::from_str_radix(channel, 16).ok())
+                .ok_or_else(|| ParseColorError(s.to_string()))
+        };
+
+        if hex.len() != 6 {
+            return Err(ParseColorError(s.to_string()));
+        }
+
+        Ok(Color {
+            red: channel(0)?,
+            green: channel(2)?,
+            blue: channel(4)?,
+        })
+    }
+}
+
+/// A [`Color`] (de)serialized as a hex string, for places like environment
+/// variables where a nested object doesn't fit.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub struct HexColor(pub Color);
+
+impl TryFrom<String> for HexColor {
+    type Error = ParseColorError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> { value.parse().map(HexColor) }
+}
+
+impl From<HexColor> for String {
+    fn from(HexColor(color): HexColor) -> Self {
+        format!("#{:02x}{:02x}{:02x}", color.red, color.green, color.blue)
+    }
+}
@@ -0,0 +1,43 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use tokio::time::{interval, MissedTickBehavior};
+
+use crate::repo::{canvas::Canvas, presence::PresenceMessage, voting::VotingRepo};
+
+const TICK: Duration = Duration::from_secs(1);
+
+/// Ends the rounds of the voted canvas, painting the winning colors and
+/// broadcasting the results, and keeps its presence clients posted on the
+/// open round every tick.
+pub async fn run_voting_rounds(voting: VotingRepo, canvas: Canvas) {
+    let mut ticker = interval(TICK);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    loop {
+        ticker.tick().await;
+
+        if voting.round().ends_at <= Utc::now() {
+            let results = voting.end_round();
+            // Results only cover the canvas' leds, held ones are skipped.
+            let _ = canvas
+                .leds
+                .set_many(results.leds.iter().map(|led| (led.led, led.color)))
+                .await;
+
+            tracing::info!(
+                "Voting round {} ended with {} voters changing {} leds",
+                results.number,
+                results.voters,
+                results.leds.len()
+            );
+            canvas
+                .presence
+                .broadcast(PresenceMessage::RoundResults(results));
+        }
+
+        canvas
+            .presence
+            .broadcast(PresenceMessage::Round(voting.round()));
+    }
+}
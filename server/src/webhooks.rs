@@ -0,0 +1,463 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use reqwest::{
+    dns::{Addrs, Name, Resolve, Resolving},
+    redirect::Policy,
+    Url,
+};
+use serde::Serialize;
+use sha2::Sha256;
+use tokio::{
+    net::lookup_host,
+    sync::broadcast::error::RecvError,
+    time::{sleep, timeout, Instant},
+};
+use uuid::Uuid;
+
+use crate::{
+    events::{AppEvent, EventBus},
+    repo::webhook::{Delivery, Webhook, WebhookRepo},
+};
+
+/// Events arriving within this window are delivered as one batch.
+const BATCH_WINDOW: Duration = Duration::from_secs(1);
+const MAX_ATTEMPTS: u32 = 5;
+const INITIAL_BACKOFF: Duration = if cfg!(test) {
+    Duration::from_millis(10)
+} else {
+    Duration::from_secs(1)
+};
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub const SIGNATURE_HEADER: &str = "x-controlmylights-signature-256";
+pub const DELIVERY_HEADER: &str = "x-controlmylights-delivery";
+
+#[derive(Serialize)]
+struct Payload<'a> {
+    webhook_id: Uuid,
+    delivery_id: Uuid,
+    events: &'a [AppEvent],
+}
+
+/// Listens on the event bus and delivers batches of matching events to every
+/// registered webhook.
+pub async fn run_webhooks(webhooks: WebhookRepo, events: EventBus) {
+    // Redirects could point a delivery anywhere, public or not.
+    let mut client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .redirect(Policy::none());
+    if !webhooks.allows_private_targets() {
+        client = client.dns_resolver(Arc::new(PublicResolver));
+    }
+    let client = client.build().expect("webhook http client");
+    let mut receiver = events.subscribe();
+
+    loop {
+        let mut batch = match receiver.recv().await {
+            Ok(event) => vec![event],
+            Err(RecvError::Lagged(skipped)) => {
+                tracing::warn!("Webhooks fell behind, skipped {skipped} events");
+                continue;
+            }
+            Err(RecvError::Closed) => break,
+        };
+
+        let deadline = Instant::now() + BATCH_WINDOW;
+        while let Ok(Ok(event)) = timeout(deadline - Instant::now(), receiver.recv()).await {
+            batch.push(event);
+        }
+
+        for (id, webhook) in webhooks.list().await {
+            let events: Vec<AppEvent> = batch
+                .iter()
+                .filter_map(|event| webhook.filter(event))
+                .collect();
+
+            if !events.is_empty() {
+                tokio::spawn(deliver(
+                    client.clone(),
+                    webhooks.clone(),
+                    id,
+                    webhook,
+                    events,
+                ));
+            }
+        }
+    }
+}
+
+/// Whether `ip` is reachable by anyone, i.e. not loopback, private,
+/// link-local or otherwise reserved for the server's own network.
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [first, second, ..] = ip.octets();
+            let shared = first == 100 && second & 0xc0 == 64;
+
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || shared)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => {
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    || ip.is_unique_local()
+                    || ip.is_unicast_link_local())
+            }
+        },
+    }
+}
+
+/// Whether every address `url` points to is public. Hosts that don't resolve
+/// count as not public.
+pub async fn is_public_target(url: &Url) -> bool {
+    let Some(host) = url.host_str() else {
+        return false;
+    };
+    // IPv6 hosts come bracketed.
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let port = url.port_or_known_default().unwrap_or_default();
+
+    match lookup_host((host, port)).await {
+        Ok(addrs) => {
+            let addrs: Vec<SocketAddr> = addrs.collect();
+            !addrs.is_empty() && addrs.iter().all(|addr| is_public(addr.ip()))
+        }
+        Err(_) => false,
+    }
+}
+
+/// Leaves every address that isn't public out of name lookups, so a webhook
+/// whose host later resolves into the server's network can't reach it.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|addr| is_public(addr.ip()))
+                .collect();
+
+            if addrs.is_empty() {
+                return Err(format!("{} has no public address", name.as_str()).into());
+            }
+
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac accepts any key length");
+    mac.update(body);
+
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+async fn deliver(
+    client: reqwest::Client,
+    webhooks: WebhookRepo,
+    webhook_id: Uuid,
+    webhook: Webhook,
+    events: Vec<AppEvent>,
+) {
+    let delivery_id = Uuid::new_v4();
+    let body = serde_json::to_vec(&Payload {
+        webhook_id,
+        delivery_id,
+        events: &events,
+    })
+    .expect("events always serialize");
+    let signature = sign(&webhook.secret, &body);
+
+    let mut backoff = INITIAL_BACKOFF;
+    let mut attempts = 0;
+    let (status, error) = loop {
+        attempts += 1;
+
+        let result = client
+            .post(&webhook.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, &signature)
+            .header(DELIVERY_HEADER, delivery_id.to_string())
+            .body(body.clone())
+            .send()
+            .await;
+
+        let (status, error) = match result {
+            Ok(response) if response.status().is_success() => {
+                break (Some(response.status()), None)
+            }
+            Ok(response) => (
+                Some(response.status()),
+                Some(format!("Responded with {}", response.status())),
+            ),
+            Err(err) => (None, Some(err.to_string())),
+        };
+
+        if attempts == MAX_ATTEMPTS {
+            break (status, error);
+        }
+
+        tracing::warn!(
+            "Webhook {webhook_id} delivery {delivery_id} failed (attempt {attempts}), retrying in {backoff:?}: {}",
+            error.as_deref().unwrap_or_default()
+        );
+        sleep(backoff).await;
+        backoff *= 2;
+    };
+
+    webhooks
+        .record_delivery(
+            webhook_id,
+            Delivery {
+                id: delivery_id,
+                events: events.iter().map(AppEvent::kind).collect(),
+                attempts,
+                status: status.map(|status| status.as_u16()),
+                success: error.is_none(),
+                error,
+                finished_at: Utc::now(),
+            },
+        )
+        .await;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use axum::{body::Bytes, http::HeaderMap, routing::post, Router};
+    use reqwest::StatusCode;
+    use tokio::{net::TcpListener, sync::mpsc};
+
+    use super::*;
+
+    const SECRET: &str = "secret";
+
+    struct Request {
+        signature: String,
+        delivery_id: String,
+        body: Bytes,
+        received_at: Instant,
+    }
+
+    /// Serves a receiver on a local port that fails the first `failures`
+    /// requests, returning the port and the requests it got.
+    async fn receiver(failures: usize) -> (u16, mpsc::UnboundedReceiver<Request>) {
+        let (sender, requests) = mpsc::unbounded_channel();
+        let failures = Arc::new(AtomicUsize::new(failures));
+
+        let app = Router::new().route(
+            "/",
+            post(move |headers: HeaderMap, body: Bytes| async move {
+                let header = |name| headers[name].to_str().unwrap().to_string();
+                let _ = sender.send(Request {
+                    signature: header(SIGNATURE_HEADER),
+                    delivery_id: header(DELIVERY_HEADER),
+                    body,
+                    received_at: Instant::now(),
+                });
+
+                let failing = failures
+                    .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                    .is_ok();
+                if failing {
+                    StatusCode::INTERNAL_SERVER_ERROR
+                } else {
+                    StatusCode::OK
+                }
+            }),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        (port, requests)
+    }
+
+    fn webhook(url: String) -> Webhook {
+        Webhook {
+            url,
+            secret: SECRET.to_string(),
+            events: vec![],
+            canvas: None,
+            led_range: None,
+        }
+    }
+
+    fn scene_applied(scene: &str) -> AppEvent {
+        AppEvent::SceneApplied {
+            canvas: "default".into(),
+            scene: scene.to_string(),
+        }
+    }
+
+    async fn next_delivery(webhooks: &WebhookRepo, id: Uuid) -> Delivery {
+        timeout(Duration::from_secs(10), async {
+            loop {
+                if let Some(delivery) = webhooks.deliveries(id).await.unwrap().pop() {
+                    return delivery;
+                }
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("delivery finished")
+    }
+
+    #[test]
+    fn sign_is_hmac_sha256() {
+        assert_eq!(
+            sign("key", b"The quick brown fox jumps over the lazy dog"),
+            "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+    }
+
+    #[test]
+    fn private_addresses_are_not_public() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "::",
+            "fe80::1",
+            "fd00::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{ip}");
+        }
+
+        for ip in ["1.1.1.1", "2606:4700:4700::1111", "::ffff:8.8.8.8"] {
+            assert!(is_public(ip.parse().unwrap()), "{ip}");
+        }
+    }
+
+    #[tokio::test]
+    async fn private_targets_are_refused() {
+        for url in [
+            "http://127.0.0.1/",
+            "http://[::1]:8080/",
+            "http://localhost:3000/",
+        ] {
+            assert!(!is_public_target(&Url::parse(url).unwrap()).await, "{url}");
+        }
+    }
+
+    #[tokio::test]
+    async fn batches_are_delivered_signed() {
+        let (port, mut requests) = receiver(0).await;
+        let webhooks = WebhookRepo::new(true);
+        let id = webhooks
+            .insert(webhook(format!("http://127.0.0.1:{port}/")))
+            .await;
+        let events = EventBus::new(16);
+        tokio::spawn(run_webhooks(webhooks.clone(), events.clone()));
+        sleep(Duration::from_millis(50)).await;
+
+        events.publish(scene_applied("first"));
+        events.publish(scene_applied("second"));
+
+        let request = requests.recv().await.unwrap();
+        let payload: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(request.signature, sign(SECRET, &request.body));
+        assert_eq!(payload["webhook_id"], id.to_string());
+        assert_eq!(payload["delivery_id"], request.delivery_id);
+        assert_eq!(payload["events"][0]["scene"], "first");
+        assert_eq!(payload["events"][1]["scene"], "second");
+
+        let delivery = next_delivery(&webhooks, id).await;
+        assert!(delivery.success);
+        assert_eq!(delivery.attempts, 1);
+        assert_eq!(delivery.events.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn failed_deliveries_are_retried_with_backoff() {
+        let (port, mut requests) = receiver(2).await;
+        let webhooks = WebhookRepo::new(true);
+        let webhook = webhook(format!("http://127.0.0.1:{port}/"));
+        let id = webhooks.insert(webhook.clone()).await;
+
+        deliver(
+            reqwest::Client::new(),
+            webhooks.clone(),
+            id,
+            webhook,
+            vec![scene_applied("scene")],
+        )
+        .await;
+
+        let delivery = next_delivery(&webhooks, id).await;
+        assert!(delivery.success);
+        assert_eq!(delivery.attempts, 3);
+        assert_eq!(delivery.status, Some(200));
+
+        let requests: Vec<Request> = (0..3).map(|_| requests.try_recv().unwrap()).collect();
+        assert!(requests
+            .iter()
+            .all(|request| request.delivery_id == requests[0].delivery_id));
+        assert!(requests[1].received_at - requests[0].received_at >= INITIAL_BACKOFF);
+        assert!(requests[2].received_at - requests[1].received_at >= INITIAL_BACKOFF * 2);
+    }
+
+    #[tokio::test]
+    async fn deliveries_give_up_after_max_attempts() {
+        let (port, _requests) = receiver(usize::MAX).await;
+        let webhooks = WebhookRepo::new(true);
+        let webhook = webhook(format!("http://127.0.0.1:{port}/"));
+        let id = webhooks.insert(webhook.clone()).await;
+
+        deliver(
+            reqwest::Client::new(),
+            webhooks.clone(),
+            id,
+            webhook,
+            vec![scene_applied("scene")],
+        )
+        .await;
+
+        let delivery = next_delivery(&webhooks, id).await;
+        assert!(!delivery.success);
+        assert_eq!(delivery.attempts, MAX_ATTEMPTS);
+        assert_eq!(delivery.status, Some(500));
+    }
+
+    #[tokio::test]
+    async fn names_resolving_to_private_addresses_are_not_delivered_to() {
+        let (port, mut requests) = receiver(0).await;
+        let webhooks = WebhookRepo::new(false);
+        let id = webhooks
+            .insert(webhook(format!("http://localhost:{port}/")))
+            .await;
+        let events = EventBus::new(16);
+        tokio::spawn(run_webhooks(webhooks.clone(), events.clone()));
+        sleep(Duration::from_millis(50)).await;
+
+        events.publish(scene_applied("scene"));
+
+        let delivery = next_delivery(&webhooks, id).await;
+        assert!(!delivery.success);
+        assert!(requests.try_recv().is_err());
+    }
+}